@@ -0,0 +1,284 @@
+// Headless front end for the cpu4 emulator so it can be driven from scripts.
+//
+//     cpu_emulation run ROM [--cycles N] [--dump-registers] [--dump-screen FILE]
+//     cpu_emulation trace ROM [--cycles N]
+//     cpu_emulation disasm ROM
+//     cpu_emulation asm SRC -o OUT
+//
+// ROMs are loaded at address 0, where cpu4 starts executing.
+
+use std::error::Error;
+use std::fmt::Write as _;
+use std::fs;
+
+use crate::cpu4::{self, asm, CPU, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+pub const USAGE: &str = "\
+usage: cpu_emulation                       run the cpu1..cpu4 demos
+       cpu_emulation run ROM [--cycles N] [--dump-registers] [--dump-screen FILE]
+       cpu_emulation trace ROM [--cycles N]
+       cpu_emulation disasm ROM
+       cpu_emulation asm SRC -o OUT";
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Command {
+    Run {
+        rom: String,
+        cycles: Option<u64>,
+        dump_registers: bool,
+        dump_screen: Option<String>,
+    },
+    Trace {
+        rom: String,
+        cycles: Option<u64>,
+    },
+    Disasm {
+        rom: String,
+    },
+    Asm {
+        source: String,
+        output: String,
+    },
+}
+
+impl Command {
+    pub fn parse(args: &[String]) -> Result<Command, String> {
+        let subcommand = args.get(1).ok_or("missing subcommand")?;
+        let allowed: &[&str] = match subcommand.as_str() {
+            "run" => &["--cycles", "--dump-registers", "--dump-screen"],
+            "trace" => &["--cycles"],
+            "disasm" => &[],
+            "asm" => &["-o", "--output"],
+            _ => return Err(format!("unknown subcommand `{}`", subcommand)),
+        };
+
+        let mut rest = args[2..].iter();
+        let mut path = None;
+        let mut cycles = None;
+        let mut dump_registers = false;
+        let mut dump_screen = None;
+        let mut output = None;
+
+        while let Some(arg) = rest.next() {
+            if arg.starts_with('-') && !allowed.contains(&arg.as_str()) {
+                return Err(format!("`{}` does not take `{}`", subcommand, arg));
+            }
+            let mut value = || {
+                rest.next()
+                    .cloned()
+                    .ok_or_else(|| format!("{} needs a value", arg))
+            };
+            match arg.as_str() {
+                "--cycles" => {
+                    let n = value()?;
+                    cycles = Some(
+                        n.parse()
+                            .map_err(|_| format!("invalid cycle count `{}`", n))?,
+                    );
+                }
+                "--dump-registers" => dump_registers = true,
+                "--dump-screen" => dump_screen = Some(value()?),
+                "-o" | "--output" => output = Some(value()?),
+                _ if path.is_none() => path = Some(arg.clone()),
+                _ => return Err(format!("unexpected argument `{}`", arg)),
+            }
+        }
+
+        let path = path.ok_or_else(|| format!("`{}` needs a file", subcommand))?;
+
+        Ok(match subcommand.as_str() {
+            "run" => Command::Run {
+                rom: path,
+                cycles,
+                dump_registers,
+                dump_screen,
+            },
+            "trace" => Command::Trace { rom: path, cycles },
+            "disasm" => Command::Disasm { rom: path },
+            _ => Command::Asm {
+                source: path,
+                output: output.ok_or("`asm` needs an output file (-o OUT)")?,
+            },
+        })
+    }
+}
+
+pub fn run(command: Command) -> Result<(), Box<dyn Error>> {
+    match command {
+        Command::Run {
+            rom,
+            cycles,
+            dump_registers,
+            dump_screen,
+        } => {
+            let mut cpu = load(&rom)?;
+            let result = execute(&mut cpu, cycles, |_| {});
+
+            if dump_registers {
+                print!("{}", registers(&cpu));
+            }
+            if let Some(path) = dump_screen {
+                fs::write(&path, screen(&cpu))
+                    .map_err(|e| format!("could not write `{}`: {}", path, e))?;
+            }
+            result
+        }
+        Command::Trace { rom, cycles } => {
+            let mut cpu = load(&rom)?;
+            execute(&mut cpu, cycles, |cpu| {
+                // the opcode was fetched successfully just before this runs
+                let addr = cpu.position_in_memory;
+                let opcode = cpu.read_opcode().unwrap_or_default();
+                let regs: Vec<String> =
+                    cpu.registers.iter().map(|r| format!("{:02x}", r)).collect();
+                println!(
+                    "{:#05x}  {:04x}  {:<18} V={} I={:#05x} SP={}",
+                    addr,
+                    opcode,
+                    cpu4::decode(opcode).to_string(),
+                    regs.join(" "),
+                    cpu.index_register,
+                    cpu.stack_pointer
+                );
+            })
+        }
+        Command::Disasm { rom } => {
+            let program = read(&rom)?;
+            for (i, chunk) in program.chunks(2).enumerate() {
+                let addr = i * 2;
+                match *chunk {
+                    [hi, lo] => {
+                        let opcode = u16::from_be_bytes([hi, lo]);
+                        println!("{:#05x}  {:04x}  {}", addr, opcode, cpu4::decode(opcode));
+                    }
+                    [byte] => println!("{:#05x}  {:02x}    DB {:#04x}", addr, byte, byte),
+                    _ => unreachable!(),
+                }
+            }
+            Ok(())
+        }
+        Command::Asm { source, output } => {
+            let text = fs::read_to_string(&source)
+                .map_err(|e| format!("could not read `{}`: {}", source, e))?;
+            let program = asm::assemble(&text).map_err(|e| format!("{}: {}", source, e))?;
+            fs::write(&output, program)
+                .map_err(|e| format!("could not write `{}`: {}", output, e))?;
+            Ok(())
+        }
+    }
+}
+
+fn read(path: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    Ok(fs::read(path).map_err(|e| format!("could not read `{}`: {}", path, e))?)
+}
+
+fn load(path: &str) -> Result<CPU, Box<dyn Error>> {
+    let mut cpu = CPU::new();
+    cpu.load(&read(path)?)?;
+    Ok(cpu)
+}
+
+/// Steps `cpu` until it halts or `cycles` opcodes have run, calling
+/// `before_step` ahead of each one.
+fn execute(
+    cpu: &mut CPU,
+    cycles: Option<u64>,
+    mut before_step: impl FnMut(&CPU),
+) -> Result<(), Box<dyn Error>> {
+    let mut executed = 0;
+    while cycles.is_none_or(|limit| executed < limit) {
+        if cpu.read_opcode().is_ok() {
+            before_step(cpu);
+        }
+        if !cpu.step()? {
+            return Ok(());
+        }
+        executed += 1;
+    }
+    Ok(())
+}
+
+fn registers(cpu: &CPU) -> String {
+    let mut out = String::new();
+    for (i, value) in cpu.registers.iter().enumerate() {
+        writeln!(out, "V{:X}={:#04x}", i, value).unwrap();
+    }
+    writeln!(out, "I={:#05x}", cpu.index_register).unwrap();
+    writeln!(out, "PC={:#05x}", cpu.position_in_memory).unwrap();
+    writeln!(out, "SP={}", cpu.stack_pointer).unwrap();
+    out
+}
+
+/// One line per row of pixels, `#` for lit and `.` for dark.
+fn screen(cpu: &CPU) -> String {
+    let mut out = String::with_capacity((SCREEN_WIDTH + 1) * SCREEN_HEIGHT);
+    for row in cpu.screen.iter() {
+        out.extend(row.iter().map(|&lit| if lit { '#' } else { '.' }));
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn parses_run() {
+        assert_eq!(
+            Command::parse(&args("cpu run rom.ch8 --cycles 10 --dump-screen out.txt")),
+            Ok(Command::Run {
+                rom: "rom.ch8".into(),
+                cycles: Some(10),
+                dump_registers: false,
+                dump_screen: Some("out.txt".into()),
+            })
+        );
+    }
+
+    #[test]
+    fn parses_asm() {
+        assert_eq!(
+            Command::parse(&args("cpu asm src.s -o out.ch8")),
+            Ok(Command::Asm {
+                source: "src.s".into(),
+                output: "out.ch8".into(),
+            })
+        );
+        assert!(Command::parse(&args("cpu asm src.s")).is_err());
+    }
+
+    #[test]
+    fn rejects_bad_arguments() {
+        assert!(Command::parse(&args("cpu")).is_err());
+        assert!(Command::parse(&args("cpu fly rom.ch8")).is_err());
+        assert!(Command::parse(&args("cpu disasm")).is_err());
+        assert!(Command::parse(&args("cpu disasm rom.ch8 --cycles 3")).is_err());
+        assert!(Command::parse(&args("cpu run rom.ch8 --cycles many")).is_err());
+    }
+
+    #[test]
+    fn runs_for_a_limited_number_of_cycles() {
+        // an endless loop adding 1 to V0
+        let program = asm::assemble("loop: ADD V0, 1\n JP loop").unwrap();
+        let mut cpu = CPU::new();
+        cpu.load(&program).unwrap();
+
+        execute(&mut cpu, Some(6), |_| {}).unwrap();
+        assert_eq!(cpu.registers[0], 3);
+        assert!(registers(&cpu).starts_with("V0=0x03\n"));
+    }
+
+    #[test]
+    fn renders_the_screen() {
+        let mut cpu = CPU::new();
+        cpu.screen[0][1] = true;
+        let screen = screen(&cpu);
+        assert_eq!(screen.lines().count(), SCREEN_HEIGHT);
+        assert!(screen.starts_with(".#.."));
+    }
+}
@@ -1,3 +1,4 @@
+#[allow(clippy::upper_case_acronyms)] // the book's name for it
 pub struct CPU {
     pub current_operation: u16,
     pub registers: [u8; 2],
@@ -29,6 +30,7 @@ impl CPU {
         let c = ((opcode & 0xF000) >> 12) as u8;
         let x = ((opcode & 0x0F00) >> 8) as u8;
         let y = ((opcode & 0x00F0) >> 4) as u8;
+        #[allow(clippy::identity_op)] // a shift of 0 keeps the nibbles lined up
         let d = ((opcode & 0x000F) >> 0) as u8;
 
         // checking to see if the pattern matches for an opcode that
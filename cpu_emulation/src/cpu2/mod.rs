@@ -1,3 +1,4 @@
+#[allow(clippy::upper_case_acronyms)] // the book's name for it
 struct CPU {
     // 16 registers as opposed to 2 as previous cpu
     registers: [u8; 16],
@@ -41,6 +42,7 @@ impl CPU {
             let c = ((opcode & 0xF000) >> 12) as u8;
             let x = ((opcode & 0x0F00) >> 8) as u8;
             let y = ((opcode & 0x00F0) >> 4) as u8;
+            #[allow(clippy::identity_op)] // a shift of 0 keeps the nibbles lined up
             let d = ((opcode & 0x000F) >> 0) as u8;
 
             // jumps out of the loop if the destructured values are
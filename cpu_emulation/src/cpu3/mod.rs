@@ -1,3 +1,4 @@
+#[allow(clippy::upper_case_acronyms)] // the book's name for it
 struct CPU {
     registers: [u8; 16],
 
@@ -33,6 +34,7 @@ impl CPU {
             let c = ((opcode & 0xF000) >> 12) as u8;
             let x = ((opcode & 0x0F00) >> 8) as u8;
             let y = ((opcode & 0x00F0) >> 4) as u8;
+            #[allow(clippy::identity_op)] // a shift of 0 keeps the nibbles lined up
             let d = ((opcode & 0x000F) >> 0) as u8;
 
             // opcode here has 16 bits or 2 bytes
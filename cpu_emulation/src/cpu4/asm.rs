@@ -0,0 +1,308 @@
+// A small two-pass assembler for the cpu4 instruction set. It reads the same
+// mnemonics that `Instruction`'s `Display` impl prints, so the output of
+// `disasm` can be fed straight back in.
+//
+//     ; comments run to the end of the line
+//     start:  CALL double      ; labels can be used wherever an address is
+//             HALT
+//     double: ADD V0, V1
+//             ADD V0, V1
+//             RET
+//
+// Numbers are decimal, `0x` hex or `0b` binary. `DW` emits a raw 16-bit word
+// and `DB` a single byte. Programs are assembled to start at address 0.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use super::{encode, Instruction, MEMORY_SIZE};
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for AsmError {}
+
+enum Operand<'a> {
+    Register(u8),
+    I,
+    Number(u16),
+    Label(&'a str),
+}
+
+struct Statement<'a> {
+    line: usize,
+    mnemonic: String,
+    operands: Vec<Operand<'a>>,
+}
+
+impl Statement<'_> {
+    fn size(&self) -> usize {
+        if self.mnemonic == "DB" {
+            1
+        } else {
+            2
+        }
+    }
+
+    fn error(&self, message: impl Into<String>) -> AsmError {
+        AsmError {
+            line: self.line,
+            message: message.into(),
+        }
+    }
+}
+
+pub fn assemble(source: &str) -> Result<Vec<u8>, AsmError> {
+    // first pass: split lines into statements and work out label addresses
+    let mut labels = HashMap::new();
+    let mut statements = Vec::new();
+    let mut addr = 0;
+
+    for (i, line) in source.lines().enumerate() {
+        let line_no = i + 1;
+        let mut text = line.split(';').next().unwrap_or("").trim();
+
+        if let Some((label, rest)) = text.split_once(':') {
+            let label = label.trim();
+            if !is_label(label) {
+                return Err(AsmError {
+                    line: line_no,
+                    message: format!("invalid label `{}`", label),
+                });
+            }
+            if labels.insert(label, addr).is_some() {
+                return Err(AsmError {
+                    line: line_no,
+                    message: format!("label `{}` is defined twice", label),
+                });
+            }
+            text = rest.trim();
+        }
+
+        if text.is_empty() {
+            continue;
+        }
+
+        let statement = parse_statement(line_no, text)?;
+        addr += statement.size();
+        if addr > MEMORY_SIZE {
+            return Err(statement.error(format!(
+                "program does not fit into {} bytes of memory",
+                MEMORY_SIZE
+            )));
+        }
+        statements.push(statement);
+    }
+
+    // second pass: resolve labels and encode
+    let mut program = Vec::with_capacity(addr);
+    for statement in &statements {
+        match statement.mnemonic.as_str() {
+            "DB" => program.push(byte_operand(statement, &labels)?),
+            "DW" => {
+                let word = match statement.operands.as_slice() {
+                    [value] => value_of(statement, value, &labels)?,
+                    _ => return Err(statement.error("DW takes one value")),
+                };
+                program.extend_from_slice(&word.to_be_bytes());
+            }
+            _ => {
+                let instruction = instruction(statement, &labels)?;
+                program.extend_from_slice(&encode(instruction).to_be_bytes());
+            }
+        }
+    }
+
+    Ok(program)
+}
+
+fn is_label(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn parse_statement(line: usize, text: &str) -> Result<Statement<'_>, AsmError> {
+    let (mnemonic, rest) = match text.split_once(char::is_whitespace) {
+        Some((mnemonic, rest)) => (mnemonic, rest.trim()),
+        None => (text, ""),
+    };
+
+    let mut operands = Vec::new();
+    if !rest.is_empty() {
+        for operand in rest.split(',') {
+            operands.push(parse_operand(line, operand.trim())?);
+        }
+    }
+
+    Ok(Statement {
+        line,
+        mnemonic: mnemonic.to_ascii_uppercase(),
+        operands,
+    })
+}
+
+fn parse_operand(line: usize, text: &str) -> Result<Operand<'_>, AsmError> {
+    if text.eq_ignore_ascii_case("I") {
+        return Ok(Operand::I);
+    }
+
+    let mut chars = text.chars();
+    if let (Some('V' | 'v'), Some(digit), None) = (chars.next(), chars.next(), chars.next()) {
+        if let Some(reg) = digit.to_digit(16) {
+            return Ok(Operand::Register(reg as u8));
+        }
+    }
+
+    let number = if let Some(hex) = text.strip_prefix("0x") {
+        u16::from_str_radix(hex, 16)
+    } else if let Some(bin) = text.strip_prefix("0b") {
+        u16::from_str_radix(bin, 2)
+    } else if text.starts_with(|c: char| c.is_ascii_digit()) {
+        text.parse()
+    } else if is_label(text) {
+        return Ok(Operand::Label(text));
+    } else {
+        return Err(AsmError {
+            line,
+            message: format!("invalid operand `{}`", text),
+        });
+    };
+
+    number.map(Operand::Number).map_err(|_| AsmError {
+        line,
+        message: format!("invalid number `{}`", text),
+    })
+}
+
+fn value_of(
+    statement: &Statement,
+    operand: &Operand,
+    labels: &HashMap<&str, usize>,
+) -> Result<u16, AsmError> {
+    match operand {
+        Operand::Number(n) => Ok(*n),
+        Operand::Label(name) => labels
+            .get(name)
+            .map(|&addr| addr as u16)
+            .ok_or_else(|| statement.error(format!("unknown label `{}`", name))),
+        _ => Err(statement.error("expected a number or label")),
+    }
+}
+
+fn byte_operand(statement: &Statement, labels: &HashMap<&str, usize>) -> Result<u8, AsmError> {
+    let value = match statement.operands.as_slice() {
+        [value] => value_of(statement, value, labels)?,
+        _ => return Err(statement.error("DB takes one value")),
+    };
+    u8::try_from(value).map_err(|_| statement.error(format!("{:#x} does not fit in a byte", value)))
+}
+
+fn instruction(
+    statement: &Statement,
+    labels: &HashMap<&str, usize>,
+) -> Result<Instruction, AsmError> {
+    use Operand::{Register, I};
+
+    let addr = |operand: &Operand| {
+        let value = value_of(statement, operand, labels)?;
+        if value > 0xFFF {
+            return Err(statement.error(format!("address {:#x} is out of range", value)));
+        }
+        Ok(value)
+    };
+    let byte = |operand: &Operand| {
+        let value = value_of(statement, operand, labels)?;
+        u8::try_from(value)
+            .map_err(|_| statement.error(format!("{:#x} does not fit in a byte", value)))
+    };
+
+    let instruction = match (statement.mnemonic.as_str(), statement.operands.as_slice()) {
+        ("HALT", []) => Instruction::Halt,
+        ("CLS", []) => Instruction::Cls,
+        ("RET", []) => Instruction::Ret,
+        ("JP", [a]) => Instruction::Jmp(addr(a)?),
+        ("CALL", [a]) => Instruction::Call(addr(a)?),
+        ("SE", [Register(x), Register(y)]) => Instruction::SeXy(*x, *y),
+        ("SE", [Register(x), kk]) => Instruction::Se(*x, byte(kk)?),
+        ("SNE", [Register(x), kk]) => Instruction::Sne(*x, byte(kk)?),
+        ("LD", [I, a]) => Instruction::LdI(addr(a)?),
+        ("LD", [Register(x), Register(y)]) => Instruction::LdXy(*x, *y),
+        ("LD", [Register(x), kk]) => Instruction::Ld(*x, byte(kk)?),
+        ("ADD", [Register(x), Register(y)]) => Instruction::AddXy(*x, *y),
+        ("ADD", [Register(x), kk]) => Instruction::Add(*x, byte(kk)?),
+        ("OR", [Register(x), Register(y)]) => Instruction::OrXy(*x, *y),
+        ("AND", [Register(x), Register(y)]) => Instruction::AndXy(*x, *y),
+        ("XOR", [Register(x), Register(y)]) => Instruction::XorXy(*x, *y),
+        ("DRW", [Register(x), Register(y), n]) => {
+            let n = byte(n)?;
+            if n > 0xF {
+                return Err(statement.error("sprites are at most 15 bytes tall"));
+            }
+            Instruction::Drw(*x, *y, n)
+        }
+        (mnemonic, _) => {
+            return Err(statement.error(format!("invalid operands for `{}`", mnemonic)));
+        }
+    };
+
+    Ok(instruction)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::decode;
+    use super::*;
+
+    #[test]
+    fn assembles_labels_and_data() {
+        let source = "
+            start:  CALL double   ; 5 + 10 + 10
+                    HALT
+            double: ADD V0, V1
+                    ADD v0, v1
+                    RET
+                    DB 0b11000000
+        ";
+        assert_eq!(
+            assemble(source).unwrap(),
+            vec![0x20, 0x04, 0x00, 0x00, 0x80, 0x14, 0x80, 0x14, 0x00, 0xEE, 0xC0]
+        );
+    }
+
+    #[test]
+    fn disassembly_round_trips() {
+        for opcode in (0..=0xFFFFu16).step_by(7) {
+            let text = decode(opcode).to_string();
+            assert_eq!(
+                assemble(&text).unwrap(),
+                opcode.to_be_bytes(),
+                "`{}` did not reassemble to {:04x}",
+                text,
+                opcode
+            );
+        }
+    }
+
+    #[test]
+    fn reports_errors_with_line_numbers() {
+        let err = assemble("CLS\nJP nowhere\n").unwrap_err();
+        assert_eq!(err.to_string(), "line 2: unknown label `nowhere`");
+
+        let err = assemble("LD V0, 0x100").unwrap_err();
+        assert_eq!(err.line, 1);
+        assert!(err.message.contains("does not fit in a byte"));
+
+        assert!(assemble("MUL V0, V1").is_err());
+    }
+}
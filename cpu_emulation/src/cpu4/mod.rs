@@ -1,71 +1,316 @@
-struct CPU {
-    registers: [u8; 16],
-    position_in_memory: usize, // program counter ("PC")
-    memory: [u8; 4096],
-    stack: [u16; 16],
-    stack_pointer: usize,
+pub mod asm;
+
+use std::error::Error;
+use std::fmt;
+
+pub const MEMORY_SIZE: usize = 4096;
+pub const STACK_SIZE: usize = 16;
+pub const SCREEN_WIDTH: usize = 64;
+pub const SCREEN_HEIGHT: usize = 32;
+
+#[allow(clippy::upper_case_acronyms)] // the book's name for it
+pub struct CPU {
+    pub registers: [u8; 16],
+    pub position_in_memory: usize, // program counter ("PC")
+    pub index_register: u16,       // "I", points at sprite data
+    pub memory: [u8; MEMORY_SIZE],
+    pub stack: [u16; STACK_SIZE],
+    pub stack_pointer: usize,
+    pub screen: [[bool; SCREEN_WIDTH]; SCREEN_HEIGHT],
+}
+
+/// A decoded opcode. `x` and `y` are register indexes, `kk` is an 8-bit
+/// constant and `addr` a 12-bit memory address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    Halt,            // 0000
+    Cls,             // 00E0
+    Ret,             // 00EE
+    Jmp(u16),        // 1nnn
+    Call(u16),       // 2nnn
+    Se(u8, u8),      // 3xkk
+    Sne(u8, u8),     // 4xkk
+    SeXy(u8, u8),    // 5xy0
+    Ld(u8, u8),      // 6xkk
+    Add(u8, u8),     // 7xkk
+    LdXy(u8, u8),    // 8xy0
+    OrXy(u8, u8),    // 8xy1
+    AndXy(u8, u8),   // 8xy2
+    XorXy(u8, u8),   // 8xy3
+    AddXy(u8, u8),   // 8xy4
+    LdI(u16),        // Annn
+    Drw(u8, u8, u8), // Dxyn
+    Unknown(u16),
+}
+
+/// Reasons the CPU stops before reaching a `0000` halt opcode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpuError {
+    UnknownOpcode {
+        opcode: u16,
+        addr: usize,
+    },
+    StackOverflow {
+        addr: usize,
+    },
+    StackUnderflow {
+        addr: usize,
+    },
+    /// The program counter is odd, so it no longer lines up with an opcode.
+    MisalignedPc(usize),
+    /// The program counter points past the last opcode in memory.
+    PcOutOfBounds(usize),
+    /// A sprite read by `Dxyn` runs past the end of memory.
+    SpriteOutOfBounds {
+        addr: usize,
+    },
+    /// The program does not fit into memory.
+    ProgramTooLarge(usize),
+}
+
+impl fmt::Display for CpuError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CpuError::UnknownOpcode { opcode, addr } => {
+                write!(f, "unknown opcode {:04x} at {:#05x}", opcode, addr)
+            }
+            CpuError::StackOverflow { addr } => write!(f, "stack overflow at {:#05x}", addr),
+            CpuError::StackUnderflow { addr } => write!(f, "stack underflow at {:#05x}", addr),
+            CpuError::MisalignedPc(pc) => write!(f, "program counter {:#05x} is not even", pc),
+            CpuError::PcOutOfBounds(pc) => {
+                write!(f, "program counter {:#05x} is outside of memory", pc)
+            }
+            CpuError::SpriteOutOfBounds { addr } => {
+                write!(
+                    f,
+                    "sprite drawn at {:#05x} reads past the end of memory",
+                    addr
+                )
+            }
+            CpuError::ProgramTooLarge(len) => write!(
+                f,
+                "program is {} bytes but memory only holds {}",
+                len, MEMORY_SIZE
+            ),
+        }
+    }
+}
+
+impl Error for CpuError {}
+
+/// Splits a raw opcode into an `Instruction`. Never fails: anything this CPU
+/// does not implement becomes `Instruction::Unknown`.
+pub fn decode(opcode: u16) -> Instruction {
+    let x = ((opcode & 0x0F00) >> 8) as u8;
+    let y = ((opcode & 0x00F0) >> 4) as u8;
+    let kk = (opcode & 0x00FF) as u8;
+    let n = (opcode & 0x000F) as u8;
+    let addr = opcode & 0x0FFF;
+
+    match opcode {
+        0x0000 => Instruction::Halt,
+        0x00E0 => Instruction::Cls,
+        0x00EE => Instruction::Ret,
+        0x1000..=0x1FFF => Instruction::Jmp(addr),
+        0x2000..=0x2FFF => Instruction::Call(addr),
+        0x3000..=0x3FFF => Instruction::Se(x, kk),
+        0x4000..=0x4FFF => Instruction::Sne(x, kk),
+        0x5000..=0x5FFF if n == 0 => Instruction::SeXy(x, y),
+        0x6000..=0x6FFF => Instruction::Ld(x, kk),
+        0x7000..=0x7FFF => Instruction::Add(x, kk),
+        0x8000..=0x8FFF => match n {
+            0 => Instruction::LdXy(x, y),
+            1 => Instruction::OrXy(x, y),
+            2 => Instruction::AndXy(x, y),
+            3 => Instruction::XorXy(x, y),
+            4 => Instruction::AddXy(x, y),
+            _ => Instruction::Unknown(opcode),
+        },
+        0xA000..=0xAFFF => Instruction::LdI(addr),
+        0xD000..=0xDFFF => Instruction::Drw(x, y, n),
+        _ => Instruction::Unknown(opcode),
+    }
+}
+
+/// The inverse of `decode`: packs an `Instruction` back into its opcode.
+pub fn encode(instruction: Instruction) -> u16 {
+    let xy = |x: u8, y: u8| (x as u16 & 0xF) << 8 | (y as u16 & 0xF) << 4;
+    let xkk = |x: u8, kk: u8| (x as u16 & 0xF) << 8 | kk as u16;
+
+    match instruction {
+        Instruction::Halt => 0x0000,
+        Instruction::Cls => 0x00E0,
+        Instruction::Ret => 0x00EE,
+        Instruction::Jmp(addr) => 0x1000 | (addr & 0x0FFF),
+        Instruction::Call(addr) => 0x2000 | (addr & 0x0FFF),
+        Instruction::Se(x, kk) => 0x3000 | xkk(x, kk),
+        Instruction::Sne(x, kk) => 0x4000 | xkk(x, kk),
+        Instruction::SeXy(x, y) => 0x5000 | xy(x, y),
+        Instruction::Ld(x, kk) => 0x6000 | xkk(x, kk),
+        Instruction::Add(x, kk) => 0x7000 | xkk(x, kk),
+        Instruction::LdXy(x, y) => 0x8000 | xy(x, y),
+        Instruction::OrXy(x, y) => 0x8001 | xy(x, y),
+        Instruction::AndXy(x, y) => 0x8002 | xy(x, y),
+        Instruction::XorXy(x, y) => 0x8003 | xy(x, y),
+        Instruction::AddXy(x, y) => 0x8004 | xy(x, y),
+        Instruction::LdI(addr) => 0xA000 | (addr & 0x0FFF),
+        Instruction::Drw(x, y, n) => 0xD000 | xy(x, y) | (n as u16 & 0xF),
+        Instruction::Unknown(opcode) => opcode,
+    }
+}
+
+/// Disassembles into the mnemonics understood by `asm::assemble`.
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Instruction::Halt => write!(f, "HALT"),
+            Instruction::Cls => write!(f, "CLS"),
+            Instruction::Ret => write!(f, "RET"),
+            Instruction::Jmp(addr) => write!(f, "JP {:#05x}", addr),
+            Instruction::Call(addr) => write!(f, "CALL {:#05x}", addr),
+            Instruction::Se(x, kk) => write!(f, "SE V{:X}, {:#04x}", x, kk),
+            Instruction::Sne(x, kk) => write!(f, "SNE V{:X}, {:#04x}", x, kk),
+            Instruction::SeXy(x, y) => write!(f, "SE V{:X}, V{:X}", x, y),
+            Instruction::Ld(x, kk) => write!(f, "LD V{:X}, {:#04x}", x, kk),
+            Instruction::Add(x, kk) => write!(f, "ADD V{:X}, {:#04x}", x, kk),
+            Instruction::LdXy(x, y) => write!(f, "LD V{:X}, V{:X}", x, y),
+            Instruction::OrXy(x, y) => write!(f, "OR V{:X}, V{:X}", x, y),
+            Instruction::AndXy(x, y) => write!(f, "AND V{:X}, V{:X}", x, y),
+            Instruction::XorXy(x, y) => write!(f, "XOR V{:X}, V{:X}", x, y),
+            Instruction::AddXy(x, y) => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Instruction::LdI(addr) => write!(f, "LD I, {:#05x}", addr),
+            Instruction::Drw(x, y, n) => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            Instruction::Unknown(opcode) => write!(f, "DW {:#06x}", opcode),
+        }
+    }
 }
 
 impl CPU {
-    fn run(&mut self) {
-        loop {
-            let op_byte1 = self.memory[self.position_in_memory] as u16;
-            let op_byte2 = self.memory[self.position_in_memory + 1] as u16;
-            let opcode: u16 = op_byte1 << 8 | op_byte2;
-
-            let x = ((opcode & 0x0F00) >> 8) as u8;
-            let y = ((opcode & 0x00F0) >> 4) as u8;
-            // let n  =       ((opcode & 0x000F) >>  4) as u8;
-            let kk = (opcode & 0x00FF) as u8;
-            let op_minor = (opcode & 0x000F) as u8;
-            let addr = opcode & 0x0FFF;
+    pub fn new() -> CPU {
+        CPU {
+            registers: [0; 16],
+            memory: [0; MEMORY_SIZE],
+            position_in_memory: 0,
+            index_register: 0,
+            stack: [0; STACK_SIZE],
+            stack_pointer: 0,
+            screen: [[false; SCREEN_WIDTH]; SCREEN_HEIGHT],
+        }
+    }
 
-            self.position_in_memory += 2;
+    /// Copies `program` into memory starting at address 0, where execution
+    /// begins.
+    pub fn load(&mut self, program: &[u8]) -> Result<(), CpuError> {
+        if program.len() > MEMORY_SIZE {
+            return Err(CpuError::ProgramTooLarge(program.len()));
+        }
+        self.memory[..program.len()].copy_from_slice(program);
+        Ok(())
+    }
 
-            match opcode {
-                0x0000 => {
-                    return;
-                }
-                0x00E0 => { /* CLEAR SCREEN */ }
-                0x00EE => {
-                    self.ret();
-                }
-                0x1000..=0x1FFF => {
-                    self.jmp(addr);
-                }
-                0x2000..=0x2FFF => {
-                    self.call(addr);
-                }
-                0x3000..=0x3FFF => {
-                    self.se(x, kk);
-                }
-                0x4000..=0x4FFF => {
-                    self.sne(x, kk);
-                }
-                0x5000..=0x5FFF => {
-                    self.se(x, y);
-                }
-                0x6000..=0x6FFF => {
-                    self.ld(x, kk);
-                }
-                0x7000..=0x7FFF => {
-                    self.add(x, kk);
+    /// Checks that the program counter points at a whole opcode.
+    fn check_pc(&self) -> Result<(), CpuError> {
+        let p = self.position_in_memory;
+
+        if !p.is_multiple_of(2) {
+            return Err(CpuError::MisalignedPc(p));
+        }
+        if p + 1 >= MEMORY_SIZE {
+            return Err(CpuError::PcOutOfBounds(p));
+        }
+        Ok(())
+    }
+
+    pub fn read_opcode(&self) -> Result<u16, CpuError> {
+        self.check_pc()?;
+
+        let p = self.position_in_memory;
+        let op_byte1 = self.memory[p] as u16;
+        let op_byte2 = self.memory[p + 1] as u16;
+
+        Ok(op_byte1 << 8 | op_byte2)
+    }
+
+    /// Runs until the program halts or the CPU hits an error.
+    pub fn run(&mut self) -> Result<(), CpuError> {
+        while self.step()? {}
+        Ok(())
+    }
+
+    /// Executes a single opcode. Returns `Ok(false)` once the program has
+    /// reached `0000`; the program counter is left pointing at it.
+    ///
+    /// After every `Ok` the program counter is even and points at a
+    /// complete opcode, and the stack pointer is within the stack.
+    pub fn step(&mut self) -> Result<bool, CpuError> {
+        let addr = self.position_in_memory;
+        let instruction = decode(self.read_opcode()?);
+
+        self.position_in_memory += 2;
+
+        match instruction {
+            Instruction::Halt => {
+                self.position_in_memory = addr;
+                return Ok(false);
+            }
+            Instruction::Cls => self.cls(),
+            Instruction::Ret => self.ret(addr)?,
+            Instruction::Jmp(nnn) => self.jmp(nnn),
+            Instruction::Call(nnn) => self.call(addr, nnn)?,
+            Instruction::Se(x, kk) => self.se(x, kk),
+            Instruction::Sne(x, kk) => self.sne(x, kk),
+            Instruction::SeXy(x, y) => self.se(x, self.registers[y as usize]),
+            Instruction::Ld(x, kk) => self.ld(x, kk),
+            Instruction::Add(x, kk) => self.add(x, kk),
+            Instruction::LdXy(x, y) => self.ld(x, self.registers[y as usize]),
+            Instruction::OrXy(x, y) => self.or_xy(x, y),
+            Instruction::AndXy(x, y) => self.and_xy(x, y),
+            Instruction::XorXy(x, y) => self.xor_xy(x, y),
+            Instruction::AddXy(x, y) => self.add_xy(x, y),
+            Instruction::LdI(nnn) => self.index_register = nnn,
+            Instruction::Drw(x, y, n) => self.drw(addr, x, y, n)?,
+            Instruction::Unknown(opcode) => {
+                return Err(CpuError::UnknownOpcode { opcode, addr });
+            }
+        }
+
+        self.check_pc()?;
+        Ok(true)
+    }
+
+    /// (00e0) CLS clears the screen
+    fn cls(&mut self) {
+        self.screen = [[false; SCREEN_WIDTH]; SCREEN_HEIGHT];
+    }
+
+    /// (dxyn) DRW draws the `n` byte sprite stored at `I` at the position
+    /// held in registers `x` and `y`. Pixels are XORed onto the screen and
+    /// wrap around its edges; `vF` is set when a lit pixel is erased.
+    fn drw(&mut self, from: usize, x: u8, y: u8, n: u8) -> Result<(), CpuError> {
+        let start = self.index_register as usize;
+        let end = start + n as usize;
+        if end > MEMORY_SIZE {
+            return Err(CpuError::SpriteOutOfBounds { addr: from });
+        }
+
+        let left = self.registers[x as usize] as usize;
+        let top = self.registers[y as usize] as usize;
+        let mut collision = false;
+
+        for (row, sprite) in self.memory[start..end].iter().enumerate() {
+            for col in 0..8 {
+                if sprite & (0x80 >> col) == 0 {
+                    continue;
                 }
-                0x8000..=0x8FFF => match op_minor {
-                    0 => self.ld(x, self.registers[y as usize]),
-                    1 => self.or_xy(x, y),
-                    2 => self.and_xy(x, y),
-                    3 => self.xor_xy(x, y),
-                    4 => {
-                        self.add_xy(x, y);
-                    }
-                    _ => {
-                        todo!("opcode: {:04x}", opcode);
-                    }
-                },
-                _ => todo!("opcode {:04x}", opcode),
+                let pixel =
+                    &mut self.screen[(top + row) % SCREEN_HEIGHT][(left + col) % SCREEN_WIDTH];
+                collision |= *pixel;
+                *pixel = !*pixel;
             }
         }
+
+        self.registers[0xF] = collision as u8;
+        Ok(())
     }
 
     /// (6xkk) LD sets the value `kk` into register `vx`
@@ -73,20 +318,23 @@ impl CPU {
         self.registers[vx as usize] = kk;
     }
 
-    /// (7xkk) Add sets the value `kk` into register `vx`
+    /// (7xkk) Add adds the value `kk` to register `vx`, wrapping on overflow
     fn add(&mut self, vx: u8, kk: u8) {
-        self.registers[vx as usize] += kk;
+        let reg = &mut self.registers[vx as usize];
+        *reg = reg.wrapping_add(kk);
     }
 
+    /// (3xkk) SE **S**kip the next opcode if register `vx` **e**quals `kk`
     fn se(&mut self, vx: u8, kk: u8) {
-        if vx == kk {
+        if self.registers[vx as usize] == kk {
             self.position_in_memory += 2;
         }
     }
 
-    /// () SNE  **S**tore if **n**ot **e**qual
+    /// (4xkk) SNE **S**kip the next opcode if register `vx` does **n**ot
+    /// **e**qual `kk`
     fn sne(&mut self, vx: u8, kk: u8) {
-        if vx != kk {
+        if self.registers[vx as usize] != kk {
             self.position_in_memory += 2;
         }
     }
@@ -97,34 +345,39 @@ impl CPU {
     }
 
     /// (2nnn) CALL sub-routine at `addr`
-    fn call(&mut self, addr: u16) {
+    fn call(&mut self, from: usize, addr: u16) -> Result<(), CpuError> {
         let sp = self.stack_pointer;
         let stack = &mut self.stack;
 
         if sp >= stack.len() {
-            panic!("Stack overflow!")
+            return Err(CpuError::StackOverflow { addr: from });
         }
 
         stack[sp] = self.position_in_memory as u16;
         self.stack_pointer += 1;
         self.position_in_memory = addr as usize;
+        Ok(())
     }
 
     /// (00ee) RET return from the current sub-routine
-    fn ret(&mut self) {
+    fn ret(&mut self, from: usize) -> Result<(), CpuError> {
         if self.stack_pointer == 0 {
-            panic!("Stack underflow");
+            return Err(CpuError::StackUnderflow { addr: from });
         }
 
         self.stack_pointer -= 1;
         self.position_in_memory = self.stack[self.stack_pointer] as usize;
+        Ok(())
     }
 
-    // (7xkk)
+    /// (8xy4) adds register `y` to register `x`; `vF` holds the carry
     fn add_xy(&mut self, x: u8, y: u8) {
-        self.registers[x as usize] += self.registers[y as usize];
+        let x_ = self.registers[x as usize];
+        let y_ = self.registers[y as usize];
 
-        // TODO: SET CARRY FLAG!!!!
+        let (val, overflow) = x_.overflowing_add(y_);
+        self.registers[x as usize] = val;
+        self.registers[0xF] = overflow as u8;
     }
 
     fn and_xy(&mut self, x: u8, y: u8) {
@@ -150,13 +403,7 @@ impl CPU {
 }
 
 pub fn run_cpu4() {
-    let mut cpu = CPU {
-        registers: [0; 16],
-        memory: [0; 4096],
-        position_in_memory: 0,
-        stack: [0; 16],
-        stack_pointer: 0,
-    };
+    let mut cpu = CPU::new();
 
     cpu.registers[0] = 5;
     cpu.registers[1] = 10;
@@ -174,9 +421,201 @@ pub fn run_cpu4() {
     cpu.memory[0x104] = 0x00;
     cpu.memory[0x105] = 0xEE;
 
-    cpu.run();
+    if let Err(e) = cpu.run() {
+        panic!("cpu4 stopped early: {}", e);
+    }
 
     assert_eq!(cpu.registers[0], 45);
 
     println!("5 + (10 * 2) + (10 * 2) = {}", cpu.registers[0]);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const MAX_STEPS: usize = 1_000;
+
+    fn cpu_with(
+        memory: Vec<u8>,
+        registers: [u8; 16],
+        stack: [u16; STACK_SIZE],
+        position_in_memory: usize,
+        stack_pointer: usize,
+    ) -> CPU {
+        let mut cpu = CPU::new();
+        cpu.memory.copy_from_slice(&memory);
+        cpu.registers = registers;
+        cpu.stack = stack;
+        cpu.position_in_memory = position_in_memory;
+        cpu.stack_pointer = stack_pointer;
+        cpu
+    }
+
+    /// Steps the CPU until it halts, errors or runs out of steps, checking
+    /// the invariants after every opcode.
+    fn check_invariants(cpu: &mut CPU) -> Result<(), TestCaseError> {
+        for _ in 0..MAX_STEPS {
+            let result = cpu.step();
+
+            prop_assert!(cpu.stack_pointer <= STACK_SIZE);
+
+            match result {
+                Ok(running) => {
+                    let pc = cpu.position_in_memory;
+                    prop_assert_eq!(pc % 2, 0, "pc {:#x} is odd", pc);
+                    prop_assert!(pc + 1 < MEMORY_SIZE, "pc {:#x} out of range", pc);
+                    if !running {
+                        return Ok(());
+                    }
+                }
+                Err(_) => return Ok(()),
+            }
+        }
+        Ok(())
+    }
+
+    /// Folds a random word onto an opcode from a family `decode` understands,
+    /// so the CPU runs for longer than it does on raw noise.
+    fn known_opcode(raw: u16) -> u16 {
+        match raw >> 12 {
+            0x0 if raw & 1 == 0 => 0x00E0,
+            0x0 => 0x00EE,
+            0x5 => raw & 0xFFF0,
+            0x8 => (raw & 0xFFF0) | ((raw & 0x000F) % 5),
+            0xA | 0xD => raw,
+            0x9 | 0xB | 0xC | 0xE | 0xF => raw & 0x7FFF,
+            _ => raw,
+        }
+    }
+
+    fn known_program() -> impl Strategy<Value = Vec<u8>> {
+        prop::collection::vec(any::<u16>(), MEMORY_SIZE / 2).prop_map(|ops| {
+            ops.into_iter()
+                .flat_map(|op| known_opcode(op).to_be_bytes())
+                .collect()
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn decode_never_panics(opcode in any::<u16>()) {
+            let _ = decode(opcode);
+        }
+
+        #[test]
+        fn encode_inverts_decode(opcode in any::<u16>()) {
+            prop_assert_eq!(encode(decode(opcode)), opcode);
+        }
+
+        #[test]
+        fn arbitrary_memory_keeps_invariants(
+            memory in prop::collection::vec(any::<u8>(), MEMORY_SIZE),
+            registers in any::<[u8; 16]>(),
+            stack in any::<[u16; STACK_SIZE]>(),
+            pc in 0usize..=MEMORY_SIZE + 1,
+            sp in 0usize..=STACK_SIZE,
+        ) {
+            let mut cpu = cpu_with(memory, registers, stack, pc, sp);
+            check_invariants(&mut cpu)?;
+        }
+
+        #[test]
+        fn known_opcodes_keep_invariants(
+            memory in known_program(),
+            registers in any::<[u8; 16]>(),
+            pc in (0usize..MEMORY_SIZE / 2).prop_map(|p| p * 2),
+        ) {
+            let mut cpu = cpu_with(memory, registers, [0; STACK_SIZE], pc, 0);
+            check_invariants(&mut cpu)?;
+        }
+    }
+
+    #[test]
+    fn pc_at_last_byte_is_misaligned() {
+        let mut cpu = CPU::new();
+        cpu.position_in_memory = 0xFFF;
+        assert_eq!(cpu.step(), Err(CpuError::MisalignedPc(0xFFF)));
+    }
+
+    #[test]
+    fn running_off_the_end_of_memory() {
+        let mut cpu = CPU::new();
+        cpu.memory[0xFFE] = 0x60; // LD V0, 0x01
+        cpu.memory[0xFFF] = 0x01;
+        cpu.position_in_memory = 0xFFE;
+        assert_eq!(cpu.step(), Err(CpuError::PcOutOfBounds(0x1000)));
+        assert_eq!(cpu.step(), Err(CpuError::PcOutOfBounds(0x1000)));
+    }
+
+    #[test]
+    fn jump_to_odd_address() {
+        let mut cpu = CPU::new();
+        cpu.memory[0x000] = 0x1F; // JP 0xFFF
+        cpu.memory[0x001] = 0xFF;
+        assert_eq!(cpu.step(), Err(CpuError::MisalignedPc(0xFFF)));
+    }
+
+    #[test]
+    fn call_with_full_stack() {
+        let mut cpu = CPU::new();
+        cpu.memory[0x000] = 0x20; // CALL 0x000
+        cpu.memory[0x001] = 0x00;
+        assert_eq!(cpu.run(), Err(CpuError::StackOverflow { addr: 0x000 }));
+        assert_eq!(cpu.stack_pointer, STACK_SIZE);
+    }
+
+    #[test]
+    fn ret_with_empty_stack() {
+        let mut cpu = CPU::new();
+        cpu.memory[0x000] = 0x00; // RET
+        cpu.memory[0x001] = 0xEE;
+        assert_eq!(cpu.step(), Err(CpuError::StackUnderflow { addr: 0x000 }));
+    }
+
+    #[test]
+    fn add_xy_sets_carry() {
+        let mut cpu = CPU::new();
+        cpu.registers[0] = 200;
+        cpu.registers[1] = 100;
+        cpu.memory[0x000] = 0x80; // ADD V0, V1
+        cpu.memory[0x001] = 0x14;
+        cpu.run().unwrap();
+        assert_eq!(cpu.registers[0], 44);
+        assert_eq!(cpu.registers[0xF], 1);
+    }
+
+    #[test]
+    fn drw_xors_sprite_and_reports_collision() {
+        let mut cpu = CPU::new();
+        cpu.load(&[
+            0xA0, 0x0A, // LD I, 0x00a
+            0xD0, 0x11, // DRW V0, V1, 1
+            0xD0, 0x11, // DRW V0, V1, 1
+            0x00, 0x00, // HALT
+            0x00, 0x00, //
+            0xC0, //       sprite: ##......
+        ])
+        .unwrap();
+        cpu.registers[0] = 63;
+        cpu.registers[1] = 31;
+
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert!(cpu.screen[31][63]);
+        assert!(cpu.screen[31][0]);
+        assert_eq!(cpu.registers[0xF], 0);
+
+        cpu.run().unwrap();
+        assert!(!cpu.screen[31][63]);
+        assert_eq!(cpu.registers[0xF], 1);
+    }
+
+    #[test]
+    fn drw_past_end_of_memory() {
+        let mut cpu = CPU::new();
+        cpu.load(&[0xAF, 0xFF, 0xD0, 0x02]).unwrap();
+        assert_eq!(cpu.run(), Err(CpuError::SpriteOutOfBounds { addr: 0x002 }));
+    }
+}
@@ -1,10 +1,30 @@
+mod cli;
 mod cpu1;
 mod cpu2;
 mod cpu3;
 mod cpu4;
 
+use std::env;
+use std::process;
+
 // This cpu setup only implements addition
 fn main() {
+    // with arguments, act as a headless runner for the cpu4 emulator
+    let args: Vec<String> = env::args().collect();
+    if args.len() > 1 {
+        let command = cli::Command::parse(&args).unwrap_or_else(|err| {
+            eprintln!("Problem parsing arguments: {}", err);
+            eprintln!("{}", cli::USAGE);
+            process::exit(2);
+        });
+
+        if let Err(e) = cli::run(command) {
+            eprintln!("Application error: {}", e);
+            process::exit(1);
+        }
+        return;
+    }
+
     // runs the first and most rudimentary version of the cpu emulation
     // only does addition and uses 32 bits of mem total - two registers (u8) and
     // one opcode (u16)
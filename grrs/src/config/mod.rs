@@ -0,0 +1,136 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// When to highlight matches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorChoice {
+    /// Only when printing to a terminal
+    Auto,
+    Always,
+    Never,
+}
+
+/// How to treat upper and lower case letters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CaseMode {
+    Sensitive,
+    Insensitive,
+    /// Ignore case unless the pattern has an upper case letter
+    Smart,
+}
+
+/// Defaults read from the config file. Anything left out falls back to the
+/// built-in default, and flags on the command line win over all of it.
+///
+/// ```toml
+/// color = "always"
+/// case = "smart"
+/// ignore = ["*.log", "target"]
+/// ```
+#[derive(Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FileConfig {
+    pub color: Option<ColorChoice>,
+    pub case: Option<CaseMode>,
+    /// Globs for files and directories to skip, matched against paths
+    /// relative to the directory being searched.
+    pub ignore: Vec<String>,
+}
+
+/// Where the config file lives: `grrs/config.toml` in the XDG config
+/// directory, which is `~/.config` unless `XDG_CONFIG_HOME` says otherwise.
+/// This holds on every platform, macOS included.
+pub fn path() -> Option<PathBuf> {
+    config_dir(env::var_os("XDG_CONFIG_HOME"), env::var_os("HOME"))
+        .map(|dir| dir.join("grrs").join("config.toml"))
+}
+
+/// The XDG config directory given the values of `XDG_CONFIG_HOME` and
+/// `HOME`. Like the spec says, a relative `XDG_CONFIG_HOME` is ignored.
+fn config_dir(xdg_config_home: Option<OsString>, home: Option<OsString>) -> Option<PathBuf> {
+    xdg_config_home
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| {
+            home.map(PathBuf::from)
+                .filter(|home| !home.as_os_str().is_empty())
+                .map(|home| home.join(".config"))
+        })
+}
+
+impl FileConfig {
+    /// Reads the config file at `path`. A missing file is the same as an
+    /// empty one.
+    pub fn load(path: &Path) -> Result<FileConfig> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(FileConfig::default()),
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("could not read config file `{}`", path.display()))
+            }
+        };
+        toml::from_str(&text).with_context(|| format!("invalid config file `{}`", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_every_setting() {
+        let config: FileConfig =
+            toml::from_str("color = \"never\"\ncase = \"smart\"\nignore = [\"*.log\"]\n").unwrap();
+        assert_eq!(
+            config,
+            FileConfig {
+                color: Some(ColorChoice::Never),
+                case: Some(CaseMode::Smart),
+                ignore: vec![String::from("*.log")],
+            }
+        );
+        assert_eq!(
+            toml::from_str::<FileConfig>("").unwrap(),
+            FileConfig::default()
+        );
+    }
+
+    #[test]
+    fn rejects_mistakes() {
+        assert!(toml::from_str::<FileConfig>("colour = \"never\"").is_err());
+        assert!(toml::from_str::<FileConfig>("case = \"upper\"").is_err());
+    }
+
+    #[test]
+    fn config_dir_follows_xdg() {
+        let home = Some(OsString::from("/home/ferris"));
+        assert_eq!(
+            config_dir(Some(OsString::from("/xdg")), home.clone()),
+            Some(PathBuf::from("/xdg"))
+        );
+        // unset, empty and relative values all fall back to ~/.config
+        for xdg in [None, Some(OsString::new()), Some(OsString::from("xdg"))] {
+            assert_eq!(
+                config_dir(xdg, home.clone()),
+                Some(PathBuf::from("/home/ferris/.config"))
+            );
+        }
+        assert_eq!(config_dir(None, None), None);
+    }
+
+    #[test]
+    fn missing_file_is_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = FileConfig::load(&dir.path().join("config.toml")).unwrap();
+        assert_eq!(config, FileConfig::default());
+    }
+}
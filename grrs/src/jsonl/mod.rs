@@ -0,0 +1,325 @@
+use crate::Searcher;
+use serde_json::{Map, Value};
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// A path to a value inside a JSON record, written like jq: `.level`,
+/// `.request.user.id` or `.tags[0]`. A lone `.` is the whole record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldPath {
+    text: String,
+    steps: Vec<Step>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Step {
+    Key(String),
+    Index(usize),
+}
+
+impl FromStr for FieldPath {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<FieldPath, String> {
+        let invalid = || format!("invalid field path `{}`", text);
+        let mut rest = text.strip_prefix('.').ok_or_else(invalid)?;
+        let mut steps = Vec::new();
+
+        while !rest.is_empty() {
+            if let Some(after) = rest.strip_prefix('[') {
+                let (index, after) = after.split_once(']').ok_or_else(invalid)?;
+                steps.push(Step::Index(index.parse().map_err(|_| invalid())?));
+                rest = after;
+            } else {
+                // a key follows the first `.`, or one after an index
+                rest = if steps.is_empty() {
+                    rest
+                } else {
+                    rest.strip_prefix('.').ok_or_else(invalid)?
+                };
+                let end = rest.find(['.', '[']).unwrap_or(rest.len());
+                if end == 0 {
+                    return Err(invalid());
+                }
+                steps.push(Step::Key(rest[..end].to_string()));
+                rest = &rest[end..];
+            }
+        }
+
+        Ok(FieldPath {
+            text: text.to_string(),
+            steps,
+        })
+    }
+}
+
+impl fmt::Display for FieldPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+impl FieldPath {
+    /// The value at this path in `record`, if there is one.
+    pub fn get<'a>(&self, record: &'a Value) -> Option<&'a Value> {
+        self.steps
+            .iter()
+            .try_fold(record, |value, step| match step {
+                Step::Key(key) => value.get(key),
+                Step::Index(index) => value.get(index),
+            })
+    }
+
+    /// The name a projected value is given: the path without its leading
+    /// `.`, so `.request.user` becomes `request.user`. The whole record,
+    /// `.`, keeps its name rather than get an empty one.
+    fn name(&self) -> &str {
+        if self.steps.is_empty() {
+            &self.text
+        } else {
+            &self.text[1..]
+        }
+    }
+}
+
+/// What to look for in JSON lines input and how to print what is found.
+#[derive(Debug, Default)]
+pub struct Query {
+    /// The field the pattern has to be found in. Without one the pattern
+    /// applies to the whole line.
+    pub field: Option<FieldPath>,
+    /// Print only these fields of each matching record, one record per
+    /// line. Without any the whole record is pretty-printed.
+    pub select: Vec<FieldPath>,
+}
+
+/// How many records a search found, and how many lines it passed over
+/// because they were not JSON.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Records {
+    pub matches: usize,
+    pub invalid: usize,
+}
+
+impl Query {
+    /// Writes every record in `reader` whose field contains the pattern
+    /// `searcher` looks for to `writer`, with every line of it prefixed with
+    /// `label` if there is one. Strings are searched as they are, other
+    /// values as JSON text, so
+    /// `--field .status --match 404` works. Blank lines are ignored, and
+    /// lines that are not JSON are counted and skipped.
+    pub fn find_records(
+        &self,
+        searcher: &Searcher,
+        mut reader: impl BufRead,
+        label: Option<&Path>,
+        mut writer: impl Write,
+        stop: &AtomicBool,
+    ) -> io::Result<Records> {
+        let mut records = Records::default();
+        let mut line = Vec::new();
+        while !stop.load(Ordering::Relaxed) && reader.read_until(b'\n', &mut line)? > 0 {
+            if !line.trim_ascii().is_empty() {
+                match serde_json::from_slice::<Value>(&line) {
+                    Ok(record) if self.is_match(searcher, &line, &record) => {
+                        self.write_record(&mut writer, label, &record)?;
+                        records.matches += 1;
+                    }
+                    Ok(_) => {}
+                    Err(_) => records.invalid += 1,
+                }
+            }
+            line.clear();
+        }
+        Ok(records)
+    }
+
+    fn is_match(&self, searcher: &Searcher, line: &[u8], record: &Value) -> bool {
+        let Some(field) = &self.field else {
+            return searcher.is_match(line);
+        };
+        match field.get(record) {
+            Some(Value::String(text)) => searcher.is_match(text.as_bytes()),
+            Some(value) => searcher.is_match(value.to_string().as_bytes()),
+            None => false,
+        }
+    }
+
+    fn write_record(
+        &self,
+        writer: &mut impl Write,
+        label: Option<&Path>,
+        record: &Value,
+    ) -> io::Result<()> {
+        let mut text = Vec::new();
+        if self.select.is_empty() {
+            serde_json::to_writer_pretty(&mut text, record)?;
+        } else {
+            // fields the record does not have come out as null, so every
+            // line has the same shape
+            let projected: Map<String, Value> = self
+                .select
+                .iter()
+                .map(|field| {
+                    let value = field.get(record).cloned().unwrap_or(Value::Null);
+                    (field.name().to_string(), value)
+                })
+                .collect();
+            serde_json::to_writer(&mut text, &projected)?;
+        }
+
+        // every line of a pretty-printed record can be traced to its file
+        for line in text.split(|&b| b == b'\n') {
+            if let Some(label) = label {
+                write!(writer, "{}:", label.display())?;
+            }
+            writer.write_all(line)?;
+            writer.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOG: &str = r#"{"level":"info","msg":"started","request":{"status":200,"tags":["a","b"]}}
+{"level":"error","msg":"failed","request":{"status":500}}
+not json
+
+{"level":"ERROR","msg":"shouting"}
+"#;
+
+    fn path(text: &str) -> FieldPath {
+        text.parse().unwrap()
+    }
+
+    fn run(query: &Query, pattern: &str, ignore_case: bool) -> (Records, String) {
+        run_labelled(query, pattern, ignore_case, None)
+    }
+
+    fn run_labelled(
+        query: &Query,
+        pattern: &str,
+        ignore_case: bool,
+        label: Option<&Path>,
+    ) -> (Records, String) {
+        let searcher = Searcher::new(pattern, ignore_case, false);
+        let mut out = Vec::new();
+        let stop = AtomicBool::new(false);
+        let records = query
+            .find_records(&searcher, LOG.as_bytes(), label, &mut out, &stop)
+            .unwrap();
+        (records, String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn parses_field_paths() {
+        assert_eq!(path(".").steps, []);
+        assert_eq!(
+            path(".request.tags[1]").steps,
+            [
+                Step::Key(String::from("request")),
+                Step::Key(String::from("tags")),
+                Step::Index(1),
+            ]
+        );
+        assert_eq!(
+            path(".[0].id").steps,
+            [Step::Index(0), Step::Key(String::from("id"))]
+        );
+        for bad in ["level", ".a..b", ".a.", ".a[x]", ".a[1", ".a[1]b"] {
+            assert!(bad.parse::<FieldPath>().is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn gets_values() {
+        let record: Value = serde_json::from_str(LOG.lines().next().unwrap()).unwrap();
+        assert_eq!(
+            path(".request.tags[1]").get(&record),
+            Some(&Value::from("b"))
+        );
+        assert_eq!(
+            path(".request.status").get(&record),
+            Some(&Value::from(200))
+        );
+        assert_eq!(path(".missing.deeper").get(&record), None);
+        assert_eq!(path(".").get(&record), Some(&record));
+    }
+
+    #[test]
+    fn matches_a_field() {
+        let query = Query {
+            field: Some(path(".level")),
+            select: vec![path(".msg")],
+        };
+        let (records, out) = run(&query, "error", false);
+        assert_eq!(
+            records,
+            Records {
+                matches: 1,
+                invalid: 1
+            }
+        );
+        assert_eq!(out, "{\"msg\":\"failed\"}\n");
+
+        let (records, _) = run(&query, "error", true);
+        assert_eq!(records.matches, 2);
+    }
+
+    #[test]
+    fn matches_other_values_as_json() {
+        let query = Query {
+            field: Some(path(".request.status")),
+            select: vec![path(".level"), path(".request.tags")],
+        };
+        let (_, out) = run(&query, "500", false);
+        assert_eq!(out, "{\"level\":\"error\",\"request.tags\":null}\n");
+    }
+
+    #[test]
+    fn pretty_prints_whole_records() {
+        let query = Query::default();
+        let (records, out) = run(&query, "shouting", false);
+        assert_eq!(records.matches, 1);
+        assert_eq!(
+            out,
+            "{\n  \"level\": \"ERROR\",\n  \"msg\": \"shouting\"\n}\n"
+        );
+    }
+
+    #[test]
+    fn labels_every_line() {
+        let label = Some(Path::new("app.log"));
+        let (_, out) = run_labelled(&Query::default(), "shouting", false, label);
+        assert_eq!(
+            out,
+            "app.log:{\napp.log:  \"level\": \"ERROR\",\napp.log:  \"msg\": \"shouting\"\napp.log:}\n"
+        );
+
+        let query = Query {
+            field: None,
+            select: vec![path(".msg")],
+        };
+        let (_, out) = run_labelled(&query, "shouting", false, label);
+        assert_eq!(out, "app.log:{\"msg\":\"shouting\"}\n");
+    }
+
+    #[test]
+    fn names_the_whole_record() {
+        let query = Query {
+            field: None,
+            select: vec![path("."), path(".msg")],
+        };
+        let (_, out) = run(&query, "shouting", false);
+        assert_eq!(
+            out,
+            "{\".\":{\"level\":\"ERROR\",\"msg\":\"shouting\"},\"msg\":\"shouting\"}\n"
+        );
+    }
+}
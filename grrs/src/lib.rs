@@ -0,0 +1,289 @@
+pub mod config;
+pub mod jsonl;
+
+use globset::GlobSet;
+use regex::bytes::{Regex, RegexBuilder};
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+const MATCH_COLOR: &str = "\x1b[1;31m";
+const PATH_COLOR: &str = "\x1b[35m";
+const RESET: &str = "\x1b[0m";
+
+/// Finds the lines containing a pattern and writes them out.
+pub struct Searcher {
+    regex: Regex,
+    color: bool,
+}
+
+impl Searcher {
+    /// Looks for `pattern` as plain text, highlighting each match when
+    /// `color` is set.
+    pub fn new(pattern: &str, ignore_case: bool, color: bool) -> Searcher {
+        let regex = RegexBuilder::new(&regex::escape(pattern))
+            .case_insensitive(ignore_case)
+            .build()
+            .expect("an escaped pattern is a valid regex");
+        Searcher { regex, color }
+    }
+
+    /// Whether `text` contains the pattern.
+    pub fn is_match(&self, text: &[u8]) -> bool {
+        self.regex.is_match(text)
+    }
+
+    /// Writes every line of `reader` that contains the pattern to `writer`,
+    /// and returns how many there were. Lines are written back byte for
+    /// byte, so a file that is not valid UTF-8 is still searched. Each line
+    /// is prefixed with `label` if there is one.
+    ///
+    /// `stop` is checked before each line, so a search can be cut short
+    /// without leaving a line half written.
+    pub fn find_matches(
+        &self,
+        mut reader: impl BufRead,
+        label: Option<&Path>,
+        mut writer: impl Write,
+        stop: &AtomicBool,
+    ) -> io::Result<usize> {
+        let mut matches = 0;
+        let mut line = Vec::new();
+        while !stop.load(Ordering::Relaxed) && reader.read_until(b'\n', &mut line)? > 0 {
+            if self.regex.is_match(&line) {
+                if let Some(label) = label {
+                    if self.color {
+                        write!(writer, "{}{}{}:", PATH_COLOR, label.display(), RESET)?;
+                    } else {
+                        write!(writer, "{}:", label.display())?;
+                    }
+                }
+                self.write_line(&mut writer, &line)?;
+                if !line.ends_with(b"\n") {
+                    writer.write_all(b"\n")?;
+                }
+                matches += 1;
+            }
+            line.clear();
+        }
+        Ok(matches)
+    }
+
+    fn write_line(&self, writer: &mut impl Write, line: &[u8]) -> io::Result<()> {
+        if !self.color {
+            return writer.write_all(line);
+        }
+        let mut last = 0;
+        for found in self.regex.find_iter(line).filter(|m| !m.is_empty()) {
+            writer.write_all(&line[last..found.start()])?;
+            writer.write_all(MATCH_COLOR.as_bytes())?;
+            writer.write_all(found.as_bytes())?;
+            writer.write_all(RESET.as_bytes())?;
+            last = found.end();
+        }
+        writer.write_all(&line[last..])
+    }
+}
+
+/// The files under `path`, or `path` itself if it is not a directory. Each
+/// directory is read in name order as the walk reaches it, so a big tree
+/// can be searched before it has all been listed. Files and directories
+/// whose path relative to `path` matches `ignore` are skipped. Symbolic
+/// links to directories below `path` are skipped rather than followed, so a
+/// link cannot cause a loop.
+pub fn files(path: &Path, ignore: GlobSet) -> Files {
+    Files {
+        root: path.to_path_buf(),
+        ignore,
+        stack: vec![vec![Ok(path.to_path_buf())].into_iter()],
+    }
+}
+
+pub struct Files {
+    root: PathBuf,
+    ignore: GlobSet,
+    stack: Vec<std::vec::IntoIter<io::Result<PathBuf>>>,
+}
+
+impl Iterator for Files {
+    type Item = io::Result<PathBuf>;
+
+    fn next(&mut self) -> Option<io::Result<PathBuf>> {
+        loop {
+            let path = match self.stack.last_mut()?.next() {
+                Some(Ok(path)) => path,
+                Some(Err(e)) => return Some(Err(e)),
+                None => {
+                    self.stack.pop();
+                    continue;
+                }
+            };
+            if let Ok(relative) = path.strip_prefix(&self.root) {
+                // the path asked for is searched whatever its name
+                if !relative.as_os_str().is_empty() && self.ignore.is_match(relative) {
+                    continue;
+                }
+            }
+            // the path asked for is followed if it is a link, the rest are not
+            let metadata = if path == self.root {
+                fs::metadata(&path)
+            } else {
+                fs::symlink_metadata(&path)
+            };
+            match metadata {
+                Ok(metadata) if metadata.is_dir() => match read_dir(&path) {
+                    Ok(entries) => self.stack.push(entries.into_iter()),
+                    Err(e) => return Some(Err(e)),
+                },
+                // a link to a directory is skipped; a broken link is left for
+                // the search to report
+                Ok(metadata) if metadata.is_symlink() && path.is_dir() => continue,
+                _ => return Some(Ok(path)),
+            }
+        }
+    }
+}
+
+fn read_dir(dir: &Path) -> io::Result<Vec<io::Result<PathBuf>>> {
+    let mut entries: Vec<_> = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect();
+    entries.sort_by(|a, b| match (a, b) {
+        (Ok(a), Ok(b)) => a.cmp(b),
+        _ => std::cmp::Ordering::Equal,
+    });
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use globset::{Glob, GlobSetBuilder};
+
+    fn find(contents: &[u8], pattern: &str) -> (usize, Vec<u8>) {
+        find_with(Searcher::new(pattern, false, false), contents)
+    }
+
+    fn find_with(searcher: Searcher, contents: &[u8]) -> (usize, Vec<u8>) {
+        let mut out = Vec::new();
+        let stop = AtomicBool::new(false);
+        let matches = searcher
+            .find_matches(contents, None, &mut out, &stop)
+            .unwrap();
+        (matches, out)
+    }
+
+    fn walk(root: &Path, ignore: &[&str]) -> Vec<PathBuf> {
+        let mut globs = GlobSetBuilder::new();
+        for glob in ignore {
+            globs.add(Glob::new(glob).unwrap());
+        }
+        files(root, globs.build().unwrap())
+            .map(|path| path.unwrap().strip_prefix(root).unwrap().to_path_buf())
+            .collect()
+    }
+
+    #[test]
+    fn finds_a_match() {
+        let (matches, out) = find(b"lorem ipsum\ndolor sit amet", "lorem");
+        assert_eq!(matches, 1);
+        assert_eq!(out, b"lorem ipsum\n");
+    }
+
+    #[test]
+    fn ends_the_last_line() {
+        let (matches, out) = find(b"one\ntwo", "o");
+        assert_eq!(matches, 2);
+        assert_eq!(out, b"one\ntwo\n");
+    }
+
+    #[test]
+    fn keeps_invalid_utf8() {
+        let (matches, out) = find(b"caf\xe9 au lait\nnothing\n", "lait");
+        assert_eq!(matches, 1);
+        assert_eq!(out, b"caf\xe9 au lait\n");
+    }
+
+    #[test]
+    fn pattern_is_plain_text() {
+        let (matches, _) = find(b"a.c\nabc\n", "a.c");
+        assert_eq!(matches, 1);
+    }
+
+    #[test]
+    fn ignores_case() {
+        let searcher = Searcher::new("émile", true, false);
+        let (matches, out) = find_with(searcher, "ÉMILE\nemile\nÉmile\n".as_bytes());
+        assert_eq!(matches, 2);
+        assert_eq!(out, "ÉMILE\nÉmile\n".as_bytes());
+    }
+
+    #[test]
+    fn highlights_matches() {
+        let searcher = Searcher::new("o", false, true);
+        let (_, out) = find_with(searcher, b"foo\n");
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "f\x1b[1;31mo\x1b[0m\x1b[1;31mo\x1b[0m\n"
+        );
+    }
+
+    #[test]
+    fn labels_lines() {
+        let mut out = Vec::new();
+        let stop = AtomicBool::new(false);
+        let label = Some(Path::new("dir/a.txt"));
+        Searcher::new("two", false, false)
+            .find_matches(&b"one\ntwo\n"[..], label, &mut out, &stop)
+            .unwrap();
+        assert_eq!(out, b"dir/a.txt:two\n");
+    }
+
+    #[test]
+    fn stops_when_asked() {
+        let mut out = Vec::new();
+        let stop = AtomicBool::new(true);
+        let matches = Searcher::new("one", false, false)
+            .find_matches(&b"one\n"[..], None, &mut out, &stop)
+            .unwrap();
+        assert_eq!(matches, 0);
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn walks_directories_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir(root.join("b")).unwrap();
+        fs::write(root.join("b/inner.txt"), "").unwrap();
+        fs::write(root.join("a.txt"), "").unwrap();
+        fs::write(root.join("c.txt"), "").unwrap();
+
+        assert_eq!(
+            walk(root, &[]),
+            ["a.txt", "b/inner.txt", "c.txt"].map(PathBuf::from)
+        );
+
+        let file = root.join("a.txt");
+        let found: Vec<_> = files(&file, GlobSet::empty()).map(Result::unwrap).collect();
+        assert_eq!(found, [file]);
+    }
+
+    #[test]
+    fn skips_ignored_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("target/debug")).unwrap();
+        fs::write(root.join("target/debug/out.txt"), "").unwrap();
+        fs::write(root.join("keep.txt"), "").unwrap();
+        fs::write(root.join("drop.log"), "").unwrap();
+        fs::create_dir(root.join("logs")).unwrap();
+        fs::write(root.join("logs/old.log"), "").unwrap();
+
+        assert_eq!(
+            walk(root, &["*.log", "target"]),
+            [PathBuf::from("keep.txt")]
+        );
+    }
+}
@@ -1,3 +1,306 @@
-fn main() {
-    println!("Hello, world!");
+use anyhow::{Context, Result};
+use clap::{CommandFactory, Parser};
+use clap_complete::Shell;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use grrs::config::{self, CaseMode, ColorChoice, FileConfig};
+use grrs::jsonl::{FieldPath, Query};
+use grrs::Searcher;
+use indicatif::{ProgressBar, ProgressStyle};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Search for a pattern in a file, or in every file under a directory, and
+/// print the lines that contain it.
+///
+/// Exits with 0 if a line was found, 1 if none was, and 2 on an error, like
+/// grep. Ctrl-C stops the search after the line being written, prints how
+/// far it got and exits with 130.
+///
+/// With --jsonl each line is read as a JSON record, and records are printed
+/// whole or with just the --select fields:
+///
+///     grrs --jsonl --field .level --match error --select .ts --select .msg app.log
+///
+/// Defaults for --color, the case mode and --ignore can be set in
+/// grrs/config.toml in the XDG config directory (usually ~/.config).
+#[derive(Parser)]
+#[command(version)]
+struct Cli {
+    /// The text to look for
+    #[arg(required_unless_present_any = ["completions", "man", "match_"])]
+    pattern: Option<String>,
+    /// The file or directory to search
+    #[arg(required_unless_present_any = ["completions", "man", "match_"])]
+    path: Option<PathBuf>,
+    /// Ignore case
+    #[arg(short, long, overrides_with_all = ["case_sensitive", "smart_case"])]
+    ignore_case: bool,
+    /// Match case exactly
+    #[arg(short = 's', long, overrides_with_all = ["ignore_case", "smart_case"])]
+    case_sensitive: bool,
+    /// Ignore case unless the pattern has an upper case letter
+    #[arg(short = 'S', long, overrides_with_all = ["ignore_case", "case_sensitive"])]
+    smart_case: bool,
+    /// When to highlight matches
+    #[arg(long, value_name = "WHEN")]
+    color: Option<ColorChoice>,
+    /// Skip files and directories matching GLOB, on top of those in the
+    /// config file
+    #[arg(long, value_name = "GLOB")]
+    ignore: Vec<String>,
+    /// Read each line as a JSON record and print the matching records
+    #[arg(long)]
+    jsonl: bool,
+    /// Look for the pattern in FIELD of each record, such as .level
+    #[arg(long, value_name = "FIELD", requires = "jsonl")]
+    field: Option<FieldPath>,
+    /// The text to look for, in place of PATTERN
+    #[arg(long = "match", value_name = "TEXT", requires = "jsonl")]
+    match_: Option<String>,
+    /// Print only this field of each matching record; can be repeated
+    #[arg(long, value_name = "FIELD", requires = "jsonl")]
+    select: Vec<FieldPath>,
+    /// Do not read the config file
+    #[arg(long)]
+    no_config: bool,
+    /// Print a completion script for SHELL and exit
+    #[arg(long, value_name = "SHELL", exclusive = true)]
+    completions: Option<Shell>,
+    /// Print a man page and exit
+    #[arg(long, exclusive = true)]
+    man: bool,
+}
+
+/// How far a search got.
+#[derive(Default)]
+struct Summary {
+    files: usize,
+    bytes: u64,
+    matches: usize,
+    errors: usize,
+}
+
+fn main() -> ExitCode {
+    let mut cli = Cli::parse();
+    if cli.match_.is_some() {
+        // the only positional argument is the path
+        if cli.path.is_some() {
+            Cli::command()
+                .error(
+                    clap::error::ErrorKind::ArgumentConflict,
+                    "the pattern can be given as PATTERN or with --match, not both",
+                )
+                .exit();
+        }
+        cli.path = cli.pattern.take().map(PathBuf::from);
+        cli.pattern = cli.match_.take();
+        if cli.path.is_none() {
+            Cli::command()
+                .error(
+                    clap::error::ErrorKind::MissingRequiredArgument,
+                    "the following required arguments were not provided:\n  <PATH>",
+                )
+                .exit();
+        }
+    }
+
+    if let Some(shell) = cli.completions {
+        clap_complete::generate(shell, &mut Cli::command(), "grrs", &mut io::stdout());
+        return ExitCode::SUCCESS;
+    }
+    if cli.man {
+        return match clap_mangen::Man::new(Cli::command()).render(&mut io::stdout()) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("grrs: could not write the man page: {}", e);
+                ExitCode::from(2)
+            }
+        };
+    }
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let handler = {
+        let stop = Arc::clone(&stop);
+        ctrlc::set_handler(move || stop.store(true, Ordering::Relaxed))
+    };
+
+    let result = handler
+        .context("could not listen for Ctrl-C")
+        .and_then(|_| run(&cli, &stop));
+    match result {
+        Ok(summary) if stop.load(Ordering::Relaxed) => {
+            eprintln!(
+                "grrs: interrupted after {} file(s) and {} byte(s), {} matching line(s) so far",
+                summary.files, summary.bytes, summary.matches
+            );
+            ExitCode::from(130)
+        }
+        Ok(summary) if summary.errors > 0 => ExitCode::from(2),
+        Ok(summary) if summary.matches > 0 => ExitCode::SUCCESS,
+        Ok(_) => ExitCode::from(1),
+        Err(e) => {
+            eprintln!("grrs: {:#}", e);
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn run(cli: &Cli, stop: &AtomicBool) -> Result<Summary> {
+    // both are required unless --completions or --man was given
+    let (Some(pattern), Some(path)) = (&cli.pattern, &cli.path) else {
+        unreachable!("clap checks for the pattern and path");
+    };
+
+    let file_config = match config::path() {
+        Some(config_path) if !cli.no_config => FileConfig::load(&config_path)?,
+        _ => FileConfig::default(),
+    };
+
+    let case = if cli.ignore_case {
+        Some(CaseMode::Insensitive)
+    } else if cli.case_sensitive {
+        Some(CaseMode::Sensitive)
+    } else if cli.smart_case {
+        Some(CaseMode::Smart)
+    } else {
+        file_config.case
+    };
+    let ignore_case = match case.unwrap_or(CaseMode::Sensitive) {
+        CaseMode::Sensitive => false,
+        CaseMode::Insensitive => true,
+        CaseMode::Smart => !pattern.chars().any(char::is_uppercase),
+    };
+    let color = match cli.color.or(file_config.color).unwrap_or(ColorChoice::Auto) {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => io::stdout().is_terminal(),
+    };
+    let ignore = globs(file_config.ignore.iter().chain(&cli.ignore))?;
+    // a pretty-printed record has no room for highlighting
+    let searcher = Searcher::new(pattern, ignore_case, color && !cli.jsonl);
+    let query = cli.jsonl.then(|| Query {
+        field: cli.field.clone(),
+        select: cli.select.clone(),
+    });
+    let search = Search { searcher, query };
+
+    let progress = progress_bar();
+    // like grep -r, only name the file when there could be more than one
+    let show_path = path.is_dir();
+    let mut out = BufWriter::new(Suspended {
+        progress: &progress,
+        inner: io::stdout().lock(),
+    });
+    let mut summary = Summary::default();
+
+    for file in grrs::files(path, ignore) {
+        if stop.load(Ordering::Relaxed) {
+            break;
+        }
+        let searched = file.map_err(anyhow::Error::from).and_then(|file| {
+            let label = show_path.then_some(file.as_path());
+            search_file(&search, &file, label, &progress, &mut out, stop)
+        });
+        match searched {
+            Ok(matches) => {
+                summary.files += 1;
+                summary.matches += matches;
+                progress.set_message(format!("{} file(s)", summary.files));
+            }
+            Err(e) => {
+                progress.suspend(|| eprintln!("grrs: {:#}", e));
+                summary.errors += 1;
+            }
+        }
+    }
+
+    out.flush().context("could not write the results")?;
+    progress.finish_and_clear();
+    summary.bytes = progress.position();
+    Ok(summary)
+}
+
+fn globs<'a>(globs: impl IntoIterator<Item = &'a String>) -> Result<GlobSet> {
+    let mut set = GlobSetBuilder::new();
+    for glob in globs {
+        set.add(Glob::new(glob).with_context(|| format!("invalid glob `{}`", glob))?);
+    }
+    Ok(set.build()?)
+}
+
+/// What to look for, and whether the input is JSON lines.
+struct Search {
+    searcher: Searcher,
+    query: Option<Query>,
+}
+
+fn search_file(
+    search: &Search,
+    path: &Path,
+    label: Option<&Path>,
+    progress: &ProgressBar,
+    out: &mut impl Write,
+    stop: &AtomicBool,
+) -> Result<usize> {
+    let file =
+        File::open(path).with_context(|| format!("could not read file `{}`", path.display()))?;
+    let reader = BufReader::new(progress.wrap_read(file));
+    let context = || format!("could not search file `{}`", path.display());
+
+    let Some(query) = &search.query else {
+        return search
+            .searcher
+            .find_matches(reader, label, out, stop)
+            .with_context(context);
+    };
+    let records = query
+        .find_records(&search.searcher, reader, label, out, stop)
+        .with_context(context)?;
+    if records.invalid > 0 {
+        progress.suspend(|| {
+            eprintln!(
+                "grrs: skipped {} line(s) of `{}` that are not JSON",
+                records.invalid,
+                path.display()
+            )
+        });
+    }
+    Ok(records.matches)
+}
+
+/// A spinner on stderr counting files and bytes, or a hidden one that only
+/// keeps count when stderr is not a terminal.
+fn progress_bar() -> ProgressBar {
+    if !io::stderr().is_terminal() {
+        return ProgressBar::hidden();
+    }
+    let progress = ProgressBar::new_spinner().with_style(
+        ProgressStyle::with_template("{spinner} {msg}, {binary_bytes} ({binary_bytes_per_sec})")
+            .expect("the template is valid"),
+    );
+    progress.set_message("0 file(s)");
+    progress.enable_steady_tick(Duration::from_millis(100));
+    progress
+}
+
+/// Hides the progress bar while writing, so results printed to the same
+/// terminal are not mixed up with it.
+struct Suspended<'a, W> {
+    progress: &'a ProgressBar,
+    inner: W,
+}
+
+impl<W: Write> Write for Suspended<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.progress.suspend(|| self.inner.write(buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.progress.suspend(|| self.inner.flush())
+    }
 }
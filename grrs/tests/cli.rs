@@ -0,0 +1,401 @@
+use assert_cmd::cargo::cargo_bin_cmd;
+use assert_cmd::Command;
+use predicates::prelude::*;
+use std::fs;
+use tempfile::{NamedTempFile, TempDir};
+
+const CONTENTS: &str = "A test\nActual content\nMore content\nAnother test\n";
+
+fn grrs() -> Command {
+    let mut cmd = cargo_bin_cmd!("grrs");
+    // keep the config file of whoever runs the tests out of it
+    cmd.env(
+        "XDG_CONFIG_HOME",
+        std::env::temp_dir().join("grrs-tests-no-config"),
+    );
+    cmd
+}
+
+/// Runs grrs with `config` as its config file.
+fn grrs_with_config(config: &str) -> (Command, TempDir) {
+    let home = TempDir::new().unwrap();
+    fs::create_dir(home.path().join("grrs")).unwrap();
+    fs::write(home.path().join("grrs/config.toml"), config).unwrap();
+    let mut cmd = grrs();
+    cmd.env("XDG_CONFIG_HOME", home.path());
+    (cmd, home)
+}
+
+fn file(contents: &str) -> NamedTempFile {
+    let file = NamedTempFile::new().unwrap();
+    fs::write(file.path(), contents).unwrap();
+    file
+}
+
+#[test]
+fn prints_matching_lines() {
+    let file = file(CONTENTS);
+    grrs()
+        .arg("test")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("A test\nAnother test\n")
+        .stderr("");
+}
+
+#[test]
+fn no_match_exits_with_1() {
+    let file = file(CONTENTS);
+    grrs()
+        .arg("missing")
+        .arg(file.path())
+        .assert()
+        .code(1)
+        .stdout("")
+        .stderr("");
+}
+
+#[test]
+fn empty_pattern_matches_every_line() {
+    let file = file(CONTENTS);
+    grrs()
+        .arg("")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout(CONTENTS);
+}
+
+#[test]
+fn empty_file_has_no_matches() {
+    let file = file("");
+    grrs().arg("").arg(file.path()).assert().code(1).stdout("");
+}
+
+#[test]
+fn missing_file_exits_with_2() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("nope.txt");
+    grrs()
+        .arg("test")
+        .arg(&path)
+        .assert()
+        .code(2)
+        .stdout("")
+        .stderr(predicate::str::contains(format!(
+            "could not read file `{}`",
+            path.display()
+        )));
+}
+
+#[test]
+fn searches_directories() {
+    let dir = TempDir::new().unwrap();
+    fs::create_dir(dir.path().join("sub")).unwrap();
+    fs::write(dir.path().join("a.txt"), CONTENTS).unwrap();
+    fs::write(dir.path().join("sub/b.txt"), "no match\none more test\n").unwrap();
+    grrs()
+        .current_dir(dir.path())
+        .args(["test", "."])
+        .assert()
+        .success()
+        .stdout("./a.txt:A test\n./a.txt:Another test\n./sub/b.txt:one more test\n")
+        .stderr("");
+}
+
+#[cfg(unix)]
+#[test]
+fn unreadable_file_in_a_directory_is_reported() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = TempDir::new().unwrap();
+    let locked = dir.path().join("a.txt");
+    fs::write(&locked, CONTENTS).unwrap();
+    fs::write(dir.path().join("b.txt"), CONTENTS).unwrap();
+    fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+    if fs::File::open(&locked).is_ok() {
+        eprintln!(
+            "skipping: `{}` is readable despite mode 000",
+            locked.display()
+        );
+        return;
+    }
+
+    // the other files are still searched, but the exit code says one failed
+    grrs()
+        .current_dir(dir.path())
+        .args(["A test", "."])
+        .assert()
+        .code(2)
+        .stdout("./b.txt:A test\n")
+        .stderr(predicate::str::contains("could not read file `./a.txt`"));
+}
+
+#[cfg(unix)]
+#[test]
+fn unreadable_file_exits_with_2() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let file = file(CONTENTS);
+    fs::set_permissions(file.path(), fs::Permissions::from_mode(0o000)).unwrap();
+    if fs::File::open(file.path()).is_ok() {
+        eprintln!(
+            "skipping: `{}` is readable despite mode 000",
+            file.path().display()
+        );
+        return;
+    }
+
+    grrs()
+        .arg("test")
+        .arg(file.path())
+        .assert()
+        .code(2)
+        .stdout("")
+        .stderr(
+            predicate::str::contains("could not read file")
+                .and(predicate::str::contains("Permission denied")),
+        );
+}
+
+// The tests above need a user that file modes apply to. These fail whoever
+// runs them, so the exit code for a file that cannot be read is always checked.
+
+#[test]
+fn file_under_a_file_exits_with_2() {
+    let file = file(CONTENTS);
+    grrs()
+        .arg("test")
+        .arg(file.path().join("child"))
+        .assert()
+        .code(2)
+        .stdout("")
+        .stderr(predicate::str::contains("could not read file"));
+}
+
+#[cfg(unix)]
+#[test]
+fn broken_symlink_in_a_directory_is_reported() {
+    let dir = TempDir::new().unwrap();
+    std::os::unix::fs::symlink("missing.txt", dir.path().join("a.txt")).unwrap();
+    fs::write(dir.path().join("b.txt"), CONTENTS).unwrap();
+
+    grrs()
+        .current_dir(dir.path())
+        .args(["A test", "."])
+        .assert()
+        .code(2)
+        .stdout("./b.txt:A test\n")
+        .stderr(predicate::str::contains("could not read file `./a.txt`"));
+}
+
+#[cfg(unix)]
+#[test]
+fn symlinked_directories_are_skipped() {
+    let dir = TempDir::new().unwrap();
+    fs::create_dir(dir.path().join("d")).unwrap();
+    fs::write(dir.path().join("d/a.txt"), CONTENTS).unwrap();
+    std::os::unix::fs::symlink("d", dir.path().join("link")).unwrap();
+    // a link back up the tree would loop if it were followed
+    std::os::unix::fs::symlink("..", dir.path().join("d/up")).unwrap();
+
+    grrs()
+        .current_dir(dir.path())
+        .args(["A test", "."])
+        .assert()
+        .success()
+        .stdout("./d/a.txt:A test\n")
+        .stderr("");
+
+    // the directory asked for is searched even through a link
+    grrs()
+        .current_dir(dir.path())
+        .args(["A test", "link"])
+        .assert()
+        .success()
+        .stdout("link/a.txt:A test\n");
+}
+
+#[test]
+fn missing_arguments_are_a_usage_error() {
+    grrs()
+        .arg("test")
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("Usage"));
+    grrs().assert().code(2);
+}
+
+#[test]
+fn searches_non_utf8_files() {
+    let file = NamedTempFile::new().unwrap();
+    fs::write(file.path(), b"caf\xe9 test\nnothing\n").unwrap();
+    grrs()
+        .arg("test")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout(predicate::eq(&b"caf\xe9 test\n"[..]));
+}
+
+#[test]
+fn reads_paths_relative_to_the_working_directory() {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("poem.txt"), CONTENTS).unwrap();
+    grrs()
+        .current_dir(dir.path())
+        .args(["content", "poem.txt"])
+        .assert()
+        .success()
+        .stdout("Actual content\nMore content\n");
+}
+
+#[test]
+fn case_flags() {
+    let file = file(CONTENTS);
+    grrs()
+        .args(["-i", "TEST"])
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("A test\nAnother test\n");
+    grrs()
+        .args(["-S", "Test"])
+        .arg(file.path())
+        .assert()
+        .code(1);
+}
+
+#[test]
+fn highlights_matches_on_request() {
+    let file = file("a test\n");
+    grrs()
+        .args(["--color", "always", "test"])
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("a \x1b[1;31mtest\x1b[0m\n");
+}
+
+#[test]
+fn config_file_sets_defaults() {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("a.txt"), CONTENTS).unwrap();
+    fs::write(dir.path().join("b.log"), CONTENTS).unwrap();
+
+    let (mut cmd, _home) =
+        grrs_with_config("case = \"insensitive\"\nignore = [\"*.log\"]\ncolor = \"never\"\n");
+    cmd.current_dir(dir.path())
+        .args(["ANOTHER", "."])
+        .assert()
+        .success()
+        .stdout("./a.txt:Another test\n");
+}
+
+#[test]
+fn flags_override_the_config_file() {
+    let file = file(CONTENTS);
+    let (mut cmd, _home) = grrs_with_config("case = \"insensitive\"\ncolor = \"always\"\n");
+    cmd.args(["-s", "--color", "never", "ANOTHER"])
+        .arg(file.path())
+        .assert()
+        .code(1);
+
+    let (mut cmd, _home) = grrs_with_config("case = \"insensitive\"\n");
+    cmd.args(["--no-config", "ANOTHER"])
+        .arg(file.path())
+        .assert()
+        .code(1);
+}
+
+#[test]
+fn invalid_config_file_exits_with_2() {
+    let file = file(CONTENTS);
+    let (mut cmd, _home) = grrs_with_config("case = \"upper\"\n");
+    cmd.arg("test")
+        .arg(file.path())
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("invalid config file"));
+}
+
+#[test]
+fn generates_completions_and_a_man_page() {
+    for shell in ["bash", "zsh", "fish"] {
+        grrs()
+            .args(["--completions", shell])
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("ignore-case"));
+    }
+    grrs()
+        .arg("--man")
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with(".ie"));
+    grrs().args(["--man", "test", "file.txt"]).assert().code(2);
+}
+
+const LOG: &str = r#"{"level":"info","msg":"started","ts":1}
+{"level":"error","msg":"failed","ts":2}
+not json
+{"level":"error","msg":"again","ts":3}
+"#;
+
+#[test]
+fn jsonl_matches_a_field() {
+    let file = file(LOG);
+    grrs()
+        .args(["--jsonl", "--field", ".level", "--match", "error"])
+        .args(["--select", ".ts", "--select", ".msg"])
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("{\"ts\":2,\"msg\":\"failed\"}\n{\"ts\":3,\"msg\":\"again\"}\n")
+        .stderr(predicate::str::contains("skipped 1 line(s)"));
+}
+
+#[test]
+fn jsonl_pretty_prints_records() {
+    let file = file(LOG);
+    grrs()
+        .args(["--jsonl", "--field", ".msg", "started"])
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("{\n  \"level\": \"info\",\n  \"msg\": \"started\",\n  \"ts\": 1\n}\n");
+    grrs()
+        .args(["--jsonl", "--field", ".level", "--match", "debug"])
+        .arg(file.path())
+        .assert()
+        .code(1)
+        .stdout("");
+}
+
+#[test]
+fn jsonl_usage_errors() {
+    let file = file(LOG);
+    // --field only makes sense with --jsonl
+    grrs()
+        .args(["--field", ".level", "error"])
+        .arg(file.path())
+        .assert()
+        .code(2);
+    grrs()
+        .args(["--jsonl", "--field", "level", "error"])
+        .arg(file.path())
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("invalid field path"));
+    grrs()
+        .args(["--jsonl", "--match", "error", "error"])
+        .arg(file.path())
+        .assert()
+        .code(2);
+    grrs()
+        .args(["--jsonl", "--match", "error"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("<PATH>"));
+}
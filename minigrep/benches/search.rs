@@ -0,0 +1,87 @@
+// Compares searching a directory tree one file at a time with `search`
+// against the worker pool `run` uses.
+//
+//     cargo bench
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use minigrep::matcher::Matcher;
+use minigrep::printer::Printer;
+use minigrep::{pool, search, walk, Config};
+use std::fs;
+use std::hint::black_box;
+use std::io;
+use std::path::Path;
+
+const FILES: usize = 200;
+const LINES_PER_FILE: usize = 5_000;
+
+fn corpus(dir: &Path) {
+    for i in 0..FILES {
+        let sub = dir.join(format!("dir{}", i % 10));
+        fs::create_dir_all(&sub).unwrap();
+
+        let mut contents = String::new();
+        for line in 0..LINES_PER_FILE {
+            if line % 500 == 0 {
+                contents.push_str("the needle is somewhere in here\n");
+            } else {
+                contents.push_str("hay hay hay, nothing but hay in this line\n");
+            }
+        }
+        fs::write(sub.join(format!("file{}.txt", i)), contents).unwrap();
+    }
+}
+
+fn bench_search(c: &mut Criterion) {
+    let dir = tempfile::tempdir().unwrap();
+    corpus(dir.path());
+    let root = dir.path().display().to_string();
+
+    let mut group = c.benchmark_group("search_tree");
+
+    group.bench_function("single_threaded_search", |b| {
+        b.iter(|| {
+            let mut found = 0;
+            for path in walk::walk(&root) {
+                let contents = fs::read_to_string(path.unwrap()).unwrap();
+                found += search(black_box("needle"), &contents).len();
+            }
+            found
+        })
+    });
+
+    let max_threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let mut threads = 1;
+    while threads <= max_threads {
+        let args: Vec<String> = ["minigrep", "needle", &root]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let config = Config::new(&args).unwrap();
+        let matcher = Matcher::new(&config).unwrap();
+
+        group.bench_with_input(
+            BenchmarkId::new("pool", threads),
+            &threads,
+            |b, &threads| {
+                b.iter(|| {
+                    let mut printer = Printer::new(&config, true);
+                    pool::search_paths(
+                        &config.paths,
+                        threads,
+                        &matcher,
+                        &mut printer,
+                        &mut io::sink(),
+                    )
+                    .unwrap()
+                })
+            },
+        );
+        threads *= 2;
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_search);
+criterion_main!(benches);
@@ -0,0 +1,108 @@
+use flate2::bufread::MultiGzDecoder;
+use std::io::{self, BufRead, BufReader};
+use xz2::bufread::XzDecoder;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+const XZ_MAGIC: &[u8] = &[0xfd, b'7', b'z', b'X', b'Z', 0x00];
+
+/// The compression formats `-z` understands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Gzip,
+    Zstd,
+    Xz,
+}
+
+/// Works out the format from the magic bytes at the start of a file rather
+/// than its extension, so renamed or extensionless files still work.
+pub fn detect(start: &[u8]) -> Option<Format> {
+    if start.starts_with(GZIP_MAGIC) {
+        Some(Format::Gzip)
+    } else if start.starts_with(ZSTD_MAGIC) {
+        Some(Format::Zstd)
+    } else if start.starts_with(XZ_MAGIC) {
+        Some(Format::Xz)
+    } else {
+        None
+    }
+}
+
+/// Wraps `reader` in a decoder if it starts with the magic bytes of a known
+/// format, and returns it as it is otherwise. Files made of several
+/// compressed streams one after the other, as `cat a.gz b.gz` produces, are
+/// read to the end.
+pub fn decoder<'a>(mut reader: impl BufRead + 'a) -> io::Result<Box<dyn BufRead + 'a>> {
+    Ok(match detect(reader.fill_buf()?) {
+        Some(Format::Gzip) => Box::new(BufReader::new(MultiGzDecoder::new(reader))),
+        Some(Format::Zstd) => Box::new(BufReader::new(zstd::Decoder::with_buffer(reader)?)),
+        Some(Format::Xz) => Box::new(BufReader::new(XzDecoder::new_multi_decoder(reader))),
+        None => Box::new(reader),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use std::io::{Read, Write};
+    use xz2::write::XzEncoder;
+
+    const TEXT: &str = "first line\nneedle\n";
+
+    fn gzip(text: &str) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(text.as_bytes()).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn decompress(bytes: &[u8]) -> String {
+        let mut text = String::new();
+        decoder(bytes).unwrap().read_to_string(&mut text).unwrap();
+        text
+    }
+
+    #[test]
+    fn detects_formats() {
+        assert_eq!(detect(&gzip(TEXT)), Some(Format::Gzip));
+        assert_eq!(detect(b"plain text"), None);
+        assert_eq!(detect(&[0x1f]), None);
+    }
+
+    #[test]
+    fn decompresses_each_format() {
+        assert_eq!(decompress(&gzip(TEXT)), TEXT);
+        assert_eq!(
+            decompress(&zstd::encode_all(TEXT.as_bytes(), 0).unwrap()),
+            TEXT
+        );
+
+        let mut xz = XzEncoder::new(Vec::new(), 6);
+        xz.write_all(TEXT.as_bytes()).unwrap();
+        assert_eq!(decompress(&xz.finish().unwrap()), TEXT);
+    }
+
+    #[test]
+    fn reads_concatenated_streams() {
+        let mut bytes = gzip("one\n");
+        bytes.extend(gzip("two\n"));
+        assert_eq!(decompress(&bytes), "one\ntwo\n");
+    }
+
+    #[test]
+    fn passes_other_input_through() {
+        assert_eq!(decompress(TEXT.as_bytes()), TEXT);
+        assert_eq!(decompress(b""), "");
+    }
+
+    #[test]
+    fn corrupt_input_is_an_error() {
+        let mut bytes = gzip(TEXT);
+        bytes.truncate(bytes.len() / 2);
+        let mut text = String::new();
+        assert!(decoder(&bytes[..])
+            .unwrap()
+            .read_to_string(&mut text)
+            .is_err());
+    }
+}
@@ -0,0 +1,82 @@
+use caseless::Caseless;
+use std::iter;
+use std::ops::Range;
+
+/// Applies full Unicode case folding to `text`, so that `ß` and `SS` or `ﬁ`
+/// and `FI` fold to the same thing. It uses the default folding rather than
+/// the Turkic one, so `I` folds to `i` and `İ` to `i̇`, while `ı` is left as
+/// it is.
+pub fn fold(text: &str) -> String {
+    caseless::default_case_fold_str(text)
+}
+
+/// A line after case folding, which remembers where each folded character
+/// came from. Folding can change the number of bytes (and characters) in a
+/// line, so offsets into the folded text have to be mapped back before they
+/// mean anything in the original.
+pub struct Folded {
+    pub text: String,
+    // for each byte of `text`, the offset in the original line of the
+    // character whose folding starts at that byte, or `None` partway
+    // through one; plus one more entry for the end of the line
+    origins: Vec<Option<usize>>,
+}
+
+impl Folded {
+    pub fn new(line: &str) -> Folded {
+        let mut text = String::with_capacity(line.len());
+        let mut origins = Vec::with_capacity(line.len() + 1);
+
+        for (offset, c) in line.char_indices() {
+            if c.is_ascii() {
+                text.push(c.to_ascii_lowercase());
+            } else {
+                text.extend(iter::once(c).default_case_fold());
+            }
+            origins.push(Some(offset));
+            origins.resize(text.len(), None);
+        }
+        origins.push(Some(line.len()));
+
+        Folded { text, origins }
+    }
+
+    /// The part of the original line that `span` of the folded text came
+    /// from, or `None` if the span starts or ends partway through the
+    /// folding of a single character, such as one `s` of the `ss` that `ß`
+    /// folds to.
+    pub fn original(&self, span: Range<usize>) -> Option<Range<usize>> {
+        Some(self.origins[span.start]?..self.origins[span.end]?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn folds_beyond_lowercase() {
+        assert_eq!(fold("Straße"), "strasse");
+        assert_eq!(fold("STRASSE"), "strasse");
+        assert_eq!(fold("ﬁle"), "file");
+        assert_eq!(fold("ΣΊΣΥΦΟΣ"), fold("σίσυφος"));
+    }
+
+    #[test]
+    fn maps_spans_back() {
+        let folded = Folded::new("Große Straße");
+        assert_eq!(folded.text, "grosse strasse");
+
+        // "strasse" covers all of "Straße"
+        assert_eq!(folded.original(7..14), Some(7..14));
+        // "osse" covers "oße"
+        assert_eq!(folded.original(2..6), Some(2..6));
+        // but half of "ß" is not a match
+        assert_eq!(folded.original(3..4), None);
+
+        let folded = Folded::new("İx");
+        assert_eq!(folded.text, "i\u{307}x");
+        assert_eq!(folded.original(0..3), Some(0..2));
+        assert_eq!(folded.original(0..1), None);
+    }
+}
@@ -0,0 +1,206 @@
+use crate::decompress;
+use crate::fold;
+use crate::input::{self, LineReader};
+use crate::pool::Stats;
+use crate::printer::Printer;
+use crate::walk;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+/// The fewest edits (inserting, deleting or changing one character) that
+/// turn `query` into some part of `line`. This is the Levenshtein distance,
+/// except that the rest of the line around the closest part is free.
+pub fn substring_distance(query: &str, line: &str) -> usize {
+    let query: Vec<char> = query.chars().collect();
+
+    // column[i] is the fewest edits that turn the first i characters of the
+    // query into a part of the line ending at the character just read.
+    // column[0] stays 0 as a match may start anywhere.
+    let mut column: Vec<usize> = (0..=query.len()).collect();
+    let mut best = query.len();
+
+    for c in line.chars() {
+        let mut diagonal = column[0];
+        for i in 1..=query.len() {
+            let change = diagonal + usize::from(query[i - 1] != c);
+            diagonal = column[i];
+            column[i] = change.min(column[i] + 1).min(column[i - 1] + 1);
+        }
+        best = best.min(column[query.len()]);
+        if best == 0 {
+            break;
+        }
+    }
+    best
+}
+
+/// A line that was close enough to the query.
+struct Ranked {
+    distance: usize,
+    path: PathBuf,
+    line_number: usize,
+    byte_offset: u64,
+    line: String,
+}
+
+/// Prints the lines under `paths` that contain `query` with at most
+/// `max_edits` edits, closest first. Lines the same distance away keep the
+/// order they were read in. Unlike a normal search nothing can be printed
+/// until every file has been read, so the lines are collected first.
+pub fn search_paths(
+    paths: &[String],
+    query: &str,
+    max_edits: usize,
+    ignore_case: bool,
+    printer: &Printer,
+    out: &mut impl Write,
+) -> io::Result<Stats> {
+    let query = if ignore_case {
+        fold::fold(query)
+    } else {
+        query.to_string()
+    };
+    let mut ranked = Vec::new();
+    let mut stats = Stats::default();
+
+    for path in paths {
+        let files: Box<dyn Iterator<Item = Result<PathBuf, String>>> = if path == input::STDIN {
+            Box::new(std::iter::once(Ok(PathBuf::from(input::STDIN))))
+        } else {
+            Box::new(walk::walk(path).map(|entry| entry.map_err(|e| e.to_string())))
+        };
+
+        for file in files {
+            let before = ranked.len();
+            let searched = file.and_then(|file| {
+                let result = if file.as_os_str() == input::STDIN {
+                    let name = Path::new(input::STDIN_NAME);
+                    open(io::stdin().lock(), printer.decompress).and_then(|reader| {
+                        rank_lines(name, reader, &query, max_edits, ignore_case, &mut ranked)
+                    })
+                } else {
+                    File::open(&file)
+                        .and_then(|reader| open(BufReader::new(reader), printer.decompress))
+                        .and_then(|reader| {
+                            rank_lines(&file, reader, &query, max_edits, ignore_case, &mut ranked)
+                        })
+                };
+                result.map_err(|e| format!("{}: {}", file.display(), e))
+            });
+
+            match searched {
+                Ok(true) => {
+                    let found = ranked.len() - before;
+                    stats.files_searched += 1;
+                    stats.files_with_matches += usize::from(found > 0);
+                    stats.matched_lines += found;
+                }
+                Ok(false) => {}
+                Err(message) => {
+                    eprintln!("minigrep: {}", message);
+                    stats.errors += 1;
+                }
+            }
+        }
+    }
+
+    // a stable sort, so ties stay in the order they were read
+    ranked.sort_by_key(|r| r.distance);
+    for r in &ranked {
+        printer.write_match(out, &r.path, r.line_number, r.byte_offset, &r.line)?;
+    }
+    Ok(stats)
+}
+
+fn open<'a>(reader: impl BufRead + 'a, decompress: bool) -> io::Result<Box<dyn BufRead + 'a>> {
+    if decompress {
+        decompress::decoder(reader)
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+/// Adds the lines from `reader` that are close enough to `query` to
+/// `ranked`. Returns false if the input was skipped as binary.
+fn rank_lines(
+    path: &Path,
+    mut reader: impl BufRead,
+    query: &str,
+    max_edits: usize,
+    ignore_case: bool,
+    ranked: &mut Vec<Ranked>,
+) -> io::Result<bool> {
+    if walk::is_binary(reader.fill_buf()?) {
+        return Ok(false);
+    }
+
+    let mut lines = LineReader::new(reader);
+    let mut line_number = 0;
+    loop {
+        let byte_offset = lines.offset();
+        let Some(line) = lines.next_line()? else {
+            break;
+        };
+        line_number += 1;
+
+        let distance = if ignore_case {
+            substring_distance(query, &fold::fold(&line))
+        } else {
+            substring_distance(query, &line)
+        };
+        if distance <= max_edits {
+            ranked.push(Ranked {
+                distance,
+                path: path.to_path_buf(),
+                line_number,
+                byte_offset,
+                line: line.into_owned(),
+            });
+        }
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distances() {
+        assert_eq!(substring_distance("rust", "I trust you"), 0);
+        // a change, a deletion and an insertion
+        assert_eq!(substring_distance("rust", "the rest"), 1);
+        assert_eq!(substring_distance("rust", "a rst"), 1);
+        assert_eq!(substring_distance("rust", "ruest"), 1);
+        // the whole query has to be found somewhere
+        assert_eq!(substring_distance("rust", ""), 4);
+        assert_eq!(substring_distance("", "anything"), 0);
+        // characters, not bytes, are counted
+        assert_eq!(substring_distance("café", "cafe"), 1);
+    }
+
+    #[test]
+    fn ranks_lines_across_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.txt"), "the rest\nnothing\n").unwrap();
+        std::fs::write(dir.path().join("b.txt"), "Rust\nrsut\nrust\n").unwrap();
+
+        let args: Vec<String> = ["minigrep", "-n", "--fuzzy", "2", "rust"]
+            .iter()
+            .map(|s| s.to_string())
+            .chain(Some(dir.path().display().to_string()))
+            .collect();
+        let config = crate::Config::new(&args).unwrap();
+        let printer = Printer::new(&config, false);
+
+        let mut out = Vec::new();
+        let stats = search_paths(&config.paths, "rust", 2, false, &printer, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "3:rust\n1:the rest\n1:Rust\n2:rsut\n"
+        );
+        assert_eq!(stats.files_searched, 2);
+        assert_eq!(stats.matched_lines, 4);
+    }
+}
@@ -0,0 +1,340 @@
+// A trigram index that lets `minigrep search --index` skip files that cannot
+// contain a match. For every file under the indexed directory it records the
+// set of three byte sequences that occur in the file's case folded text.
+// A file can only contain a plain text pattern if it contains every trigram
+// of the folded pattern, so only those files need to be read. Folding both
+// sides makes one index serve case sensitive and insensitive searches alike.
+//
+// Each entry also keeps the file's size and modification time. Rebuilding
+// the index only rereads files where those changed, and a search treats
+// any file that changed since it was indexed as a candidate, so results stay
+// correct even with a stale index.
+
+use crate::{fold, walk, Config, OutputMode};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Name of the index file kept at the top of an indexed directory. It is
+/// hidden, so searches do not look inside it.
+pub const INDEX_FILE: &str = ".minigrep-index";
+
+/// Bumped whenever the layout of the index file changes.
+const VERSION: u32 = 1;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Index {
+    version: u32,
+    /// Keyed by path relative to the indexed directory.
+    files: BTreeMap<PathBuf, Entry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Entry {
+    size: u64,
+    /// Nanoseconds since the Unix epoch.
+    modified: u64,
+    /// Sorted trigrams of the folded contents, or `None` for binary files,
+    /// which searches skip anyway.
+    trigrams: Option<Vec<u32>>,
+}
+
+/// What `Index::update` did.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Update {
+    /// Files read because they are new or changed.
+    pub indexed: usize,
+    /// Files whose entries were kept as they were.
+    pub unchanged: usize,
+    /// Entries dropped because their file is gone.
+    pub removed: usize,
+    /// Files that could not be read.
+    pub errors: usize,
+}
+
+impl Index {
+    /// Reads the index kept in `dir`.
+    pub fn load(dir: &Path) -> io::Result<Index> {
+        let file = File::open(dir.join(INDEX_FILE))?;
+        let index: Index = serde_json::from_reader(BufReader::new(file))?;
+        if index.version != VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "index was made by another version of minigrep",
+            ));
+        }
+        Ok(index)
+    }
+
+    /// Writes the index into `dir`, replacing any older one in one step.
+    pub fn save(&self, dir: &Path) -> io::Result<()> {
+        let temp = dir.join(format!("{}.{}", INDEX_FILE, std::process::id()));
+        let written = File::create(&temp).and_then(|file| {
+            let mut out = BufWriter::new(file);
+            serde_json::to_writer(&mut out, self)?;
+            out.into_inner()?.sync_all()
+        });
+        let result = written.and_then(|_| fs::rename(&temp, dir.join(INDEX_FILE)));
+        if result.is_err() {
+            let _ = fs::remove_file(&temp);
+        }
+        result
+    }
+
+    /// Brings the index in line with the files under `dir`, rereading only
+    /// the ones that are new or whose size or modification time changed.
+    pub fn update(&mut self, dir: &Path) -> Update {
+        self.version = VERSION;
+        let mut update = Update::default();
+        let mut files = BTreeMap::new();
+
+        for path in walk::walk(&dir.to_string_lossy()) {
+            let entry = path.map_err(io::Error::other).and_then(|path| {
+                let relative = path.strip_prefix(dir).unwrap_or(&path).to_path_buf();
+                let named =
+                    |e: io::Error| io::Error::new(e.kind(), format!("{}: {}", path.display(), e));
+                let (size, modified) = stamp(&path).map_err(named)?;
+                match self.files.remove(&relative) {
+                    Some(entry) if entry.size == size && entry.modified == modified => {
+                        update.unchanged += 1;
+                        Ok((relative, entry))
+                    }
+                    _ => {
+                        let trigrams = file_trigrams(&fs::read(&path).map_err(named)?);
+                        update.indexed += 1;
+                        Ok((
+                            relative,
+                            Entry {
+                                size,
+                                modified,
+                                trigrams,
+                            },
+                        ))
+                    }
+                }
+            });
+
+            match entry {
+                Ok((relative, entry)) => {
+                    files.insert(relative, entry);
+                }
+                Err(e) => {
+                    eprintln!("minigrep: {}", e);
+                    update.errors += 1;
+                }
+            }
+        }
+
+        update.removed = self.files.len();
+        self.files = files;
+        update
+    }
+
+    /// The files under `dir` that need searching for any of `patterns`, in
+    /// the order a normal search would visit them: those whose trigrams
+    /// allow a match, and any that changed since they were indexed. With no
+    /// patterns to go on every file is a candidate.
+    pub fn candidates(&self, dir: &Path, patterns: Option<&[String]>) -> Vec<PathBuf> {
+        let wanted: Option<Vec<Vec<u32>>> = patterns.and_then(|patterns| {
+            patterns
+                .iter()
+                .map(|pattern| {
+                    let trigrams = trigrams(fold::fold(pattern).as_bytes());
+                    // too short to rule anything out
+                    (!trigrams.is_empty()).then_some(trigrams)
+                })
+                .collect()
+        });
+
+        walk::walk(&dir.to_string_lossy())
+            // unreadable paths are left for the search to report
+            .filter_map(Result::ok)
+            .filter(|path| {
+                let relative = path.strip_prefix(dir).unwrap_or(path);
+                let Some(entry) = self.files.get(relative) else {
+                    return true;
+                };
+                let Ok((size, modified)) = stamp(path) else {
+                    return true;
+                };
+                if entry.size != size || entry.modified != modified {
+                    return true;
+                }
+                match (&entry.trigrams, &wanted) {
+                    (None, _) => false,
+                    (Some(_), None) => true,
+                    (Some(have), Some(wanted)) => wanted
+                        .iter()
+                        .any(|pattern| pattern.iter().all(|t| have.binary_search(t).is_ok())),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Builds or refreshes the index kept in `dir`.
+pub fn build(dir: &Path) -> Result<Update, Box<dyn Error>> {
+    if !dir.is_dir() {
+        return Err(format!("`{}` is not a directory", dir.display()).into());
+    }
+    // start again from scratch if the old index cannot be used
+    let mut index = Index::load(dir).unwrap_or_default();
+    let update = index.update(dir);
+    index
+        .save(dir)
+        .map_err(|e| format!("could not write the index in `{}`: {}", dir.display(), e))?;
+    Ok(update)
+}
+
+/// The files to search for `config` using the indexes in each of its paths,
+/// which must all be indexed directories.
+pub fn candidates(config: &Config) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    // regexes, inverted matches, -c and -L need to see files without the text
+    let narrow = !config.regex
+        && !config.invert
+        && matches!(
+            config.output,
+            OutputMode::Lines | OutputMode::FilesWithMatches
+        );
+    let patterns = narrow.then_some(config.patterns.as_slice());
+
+    let mut files = Vec::new();
+    for path in &config.paths {
+        let dir = Path::new(path);
+        let index = Index::load(dir).map_err(|e| {
+            format!(
+                "no usable index in `{}` ({}); run `minigrep index {}` first",
+                path, e, path
+            )
+        })?;
+        files.extend(index.candidates(dir, patterns));
+    }
+    Ok(files)
+}
+
+fn stamp(path: &Path) -> io::Result<(u64, u64)> {
+    let metadata = fs::metadata(path)?;
+    let modified = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos() as u64);
+    Ok((metadata.len(), modified))
+}
+
+fn file_trigrams(contents: &[u8]) -> Option<Vec<u32>> {
+    if walk::is_binary(contents) {
+        return None;
+    }
+    let text = String::from_utf8_lossy(contents);
+    Some(trigrams(fold::fold(&text).as_bytes()))
+}
+
+/// Every distinct run of three bytes in `bytes`, sorted.
+fn trigrams(bytes: &[u8]) -> Vec<u32> {
+    let mut trigrams: Vec<u32> = bytes
+        .windows(3)
+        .map(|w| u32::from_be_bytes([0, w[0], w[1], w[2]]))
+        .collect();
+    trigrams.sort_unstable();
+    trigrams.dedup();
+    trigrams
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(dir: &Path, files: &[PathBuf]) -> Vec<String> {
+        files
+            .iter()
+            .map(|f| f.strip_prefix(dir).unwrap().display().to_string())
+            .collect()
+    }
+
+    fn patterns(patterns: &[&str]) -> Vec<String> {
+        patterns.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn narrows_candidates() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir(root.join("sub")).unwrap();
+        fs::write(root.join("a.txt"), "the needle is here").unwrap();
+        fs::write(root.join("b.txt"), "only hay").unwrap();
+        fs::write(root.join("sub/c.txt"), "NEEDLE in caps").unwrap();
+        fs::write(root.join("d.bin"), b"needle\0").unwrap();
+
+        let mut index = Index::default();
+        let update = index.update(root);
+        assert_eq!(update.indexed, 4);
+
+        let found = index.candidates(root, Some(&patterns(&["needle"])));
+        assert_eq!(names(root, &found), vec!["a.txt", "sub/c.txt"]);
+
+        let found = index.candidates(root, Some(&patterns(&["hay", "caps"])));
+        assert_eq!(names(root, &found), vec!["b.txt", "sub/c.txt"]);
+
+        // too short to narrow anything down
+        let found = index.candidates(root, Some(&patterns(&["ha"])));
+        assert_eq!(found.len(), 3);
+        assert_eq!(index.candidates(root, None).len(), 3);
+    }
+
+    #[test]
+    fn updates_incrementally() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::write(root.join("a.txt"), "alpha").unwrap();
+        fs::write(root.join("b.txt"), "beta").unwrap();
+        build(root).unwrap();
+
+        fs::write(root.join("b.txt"), "beta gamma, now longer").unwrap();
+        fs::remove_file(root.join("a.txt")).unwrap();
+        fs::write(root.join("c.txt"), "gamma").unwrap();
+
+        // a stale index still finds the changed and new files
+        let index = Index::load(root).unwrap();
+        let found = index.candidates(root, Some(&patterns(&["gamma"])));
+        assert_eq!(names(root, &found), vec!["b.txt", "c.txt"]);
+
+        let update = build(root).unwrap();
+        assert_eq!(
+            update,
+            Update {
+                indexed: 2,
+                unchanged: 0,
+                removed: 1,
+                errors: 0,
+            }
+        );
+        let update = build(root).unwrap();
+        assert_eq!(update.unchanged, 2);
+        assert_eq!(update.indexed, 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn counts_files_that_cannot_be_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::write(root.join("a.txt"), "alpha").unwrap();
+        std::os::unix::fs::symlink("missing.txt", root.join("b.txt")).unwrap();
+
+        let update = build(root).unwrap();
+        assert_eq!(update.indexed, 1);
+        assert_eq!(update.errors, 1);
+    }
+
+    #[test]
+    fn candidates_need_an_index() {
+        let dir = tempfile::tempdir().unwrap();
+        let args = ["minigrep", "x", &dir.path().display().to_string()].map(String::from);
+        let config = Config::new(&args).unwrap();
+        assert!(candidates(&config).is_err());
+    }
+}
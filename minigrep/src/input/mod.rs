@@ -0,0 +1,93 @@
+use std::borrow::Cow;
+use std::io::{self, BufRead};
+use std::str;
+
+/// The path that stands for standard input, as in most Unix tools.
+pub const STDIN: &str = "-";
+
+/// The name shown for standard input in the output.
+pub const STDIN_NAME: &str = "(standard input)";
+
+/// Reads `reader` one line at a time, so only the current line is held in
+/// memory. Line endings (`\n` or `\r\n`) are stripped and bytes that are not
+/// valid UTF-8 become U+FFFD rather than failing the whole read.
+pub struct LineReader<R> {
+    reader: R,
+    buf: Vec<u8>,
+    offset: u64,
+}
+
+impl<R: BufRead> LineReader<R> {
+    pub fn new(reader: R) -> LineReader<R> {
+        LineReader {
+            reader,
+            buf: Vec::new(),
+            offset: 0,
+        }
+    }
+
+    /// Byte offset of the start of the next line.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// The next line, or `None` at the end of the input. The line borrows
+    /// the reader's buffer unless it had to be repaired.
+    pub fn next_line(&mut self) -> io::Result<Option<Cow<'_, str>>> {
+        self.buf.clear();
+        let read = self.reader.read_until(b'\n', &mut self.buf)?;
+        if read == 0 {
+            return Ok(None);
+        }
+        self.offset += read as u64;
+
+        if self.buf.ends_with(b"\n") {
+            self.buf.pop();
+            if self.buf.ends_with(b"\r") {
+                self.buf.pop();
+            }
+        }
+        // checking with from_utf8 first is much faster for the common case
+        // of a valid line
+        match str::from_utf8(&self.buf) {
+            Ok(line) => Ok(Some(Cow::Borrowed(line))),
+            Err(_) => Ok(Some(String::from_utf8_lossy(&self.buf))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collect(input: &[u8]) -> Vec<String> {
+        let mut reader = LineReader::new(input);
+        let mut lines = Vec::new();
+        while let Some(line) = reader.next_line().unwrap() {
+            lines.push(line.into_owned());
+        }
+        lines
+    }
+
+    #[test]
+    fn strips_line_endings() {
+        assert_eq!(collect(b"one\ntwo\r\nthree"), vec!["one", "two", "three"]);
+        assert_eq!(collect(b"one\n\n"), vec!["one", ""]);
+        assert!(collect(b"").is_empty());
+    }
+
+    #[test]
+    fn tracks_offsets() {
+        let mut reader = LineReader::new(&b"one\r\ntwo\n"[..]);
+        assert_eq!(reader.offset(), 0);
+        reader.next_line().unwrap();
+        assert_eq!(reader.offset(), 5);
+        reader.next_line().unwrap();
+        assert_eq!(reader.offset(), 9);
+    }
+
+    #[test]
+    fn invalid_utf8_is_replaced() {
+        assert_eq!(collect(b"caf\xe9\nok\n"), vec!["caf\u{FFFD}", "ok"]);
+    }
+}
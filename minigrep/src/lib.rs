@@ -1,41 +1,367 @@
+pub mod decompress;
+pub mod fold;
+pub mod fuzzy;
+pub mod index;
+pub mod input;
+pub mod matcher;
+pub mod pool;
+pub mod printer;
+pub mod rewrite;
+pub mod tui;
+pub mod walk;
+
+use matcher::Matcher;
+use printer::Printer;
+use regex::Regex;
+use std::env;
 use std::error::Error;
 use std::fs;
-use std::env;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::thread;
 
 pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
-    let contents = fs::read_to_string(config.filename)?;
+    if config.interactive {
+        return tui::run(config);
+    }
+
+    // compile the pattern first so a typo is reported even if the file is missing
+    let matcher = Matcher::new(&config)?;
 
-let results = if config.case_sensitive {
-    search_case_insensitive(&config.query, &contents)
-} else {
-    search(&config.query, &contents)
-};
+    // like grep, only name the file when there could be more than one
+    let show_path = config.paths.len() > 1 || Path::new(&config.paths[0]).is_dir();
+    let mut printer = Printer::new(&config, show_path);
+    let mut out = BufWriter::new(io::stdout().lock());
 
-    for line in results {
-        println!("{}", line)
+    let stats = match config.fuzzy {
+        Some(max_edits) => fuzzy::search_paths(
+            &config.paths,
+            &config.patterns[0],
+            max_edits,
+            config.ignore_case(),
+            &printer,
+            &mut out,
+        )?,
+        None if config.index => pool::search_files(
+            &index::candidates(&config)?,
+            config.threads,
+            &matcher,
+            &mut printer,
+            &mut out,
+        )?,
+        None => pool::search_paths(
+            &config.paths,
+            config.threads,
+            &matcher,
+            &mut printer,
+            &mut out,
+        )?,
+    };
+    if config.json {
+        printer.write_summary(&mut out, &stats)?;
     }
+    out.flush()?;
 
+    if stats.errors > 0 {
+        return Err(format!("{} path(s) could not be searched", stats.errors).into());
+    }
     Ok(())
 }
 
+/// How upper and lower case letters in the query are matched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaseMode {
+    Sensitive,
+    Insensitive,
+    /// Insensitive unless the query contains an upper case letter.
+    Smart,
+}
+
+/// When to colour the output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorChoice {
+    Always,
+    Never,
+    /// Only when stdout is a terminal.
+    Auto,
+}
+
+/// What to print for each file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputMode {
+    /// The selected lines themselves.
+    Lines,
+    /// How many lines were selected.
+    Count,
+    /// Just the path, if any line was selected.
+    FilesWithMatches,
+    /// Just the path, if no line was selected.
+    FilesWithoutMatch,
+}
+
 pub struct Config {
-    pub query: String,
-    pub filename: String,
-    pub case_sensitive: bool,
+    /// What to look for. A line is selected if any of them matches.
+    pub patterns: Vec<String>,
+    /// Files and directories to search. Directories are searched recursively
+    /// and `-` reads standard input.
+    pub paths: Vec<String>,
+    pub case: CaseMode,
+    pub regex: bool,
+    /// Only match whole words.
+    pub word: bool,
+    pub line_number: bool,
+    pub before_context: usize,
+    pub after_context: usize,
+    pub color: ColorChoice,
+    /// Select the lines that do not match instead.
+    pub invert: bool,
+    /// Stop reading a file after this many selected lines.
+    pub max_count: Option<usize>,
+    pub output: OutputMode,
+    /// Print one JSON object per line instead of grep's format.
+    pub json: bool,
+    /// Print selected lines with each match replaced by this. In regex mode
+    /// `$1` or `${name}` refers to a capture group.
+    pub replace: Option<String>,
+    /// Rewrite files with the replacement instead of printing them.
+    pub in_place: bool,
+    /// Match lines within this many edits of the pattern, closest first.
+    pub fuzzy: Option<usize>,
+    /// Search the contents of gzip, zstd and xz compressed files.
+    pub decompress: bool,
+    /// Search a single file in a terminal UI, editing the query live.
+    pub interactive: bool,
+    /// Only search the files that the index in each directory says could
+    /// match. Built with `minigrep index DIR`.
+    pub index: bool,
+    /// How many files to search at once.
+    pub threads: usize,
 }
 
 impl Config {
-    pub fn new(args: &[String]) -> Result<Config, &str> {
-        if args.len() < 3 {
-            return Err("not enough arguments");
+    /// Parses the command line. `-i`, `-s` and `-S` override the
+    /// `CASE_INSENSITIVE` environment variable; when several are given the
+    /// last one wins. Patterns given with `-e` or read with `-f` replace the
+    /// `QUERY` argument, so every positional argument is then a path.
+    pub fn new(args: &[String]) -> Result<Config, String> {
+        Config::parse(args, env::var("CASE_INSENSITIVE").is_ok())
+    }
+
+    fn parse(args: &[String], env_case_insensitive: bool) -> Result<Config, String> {
+        let mut regex = false;
+        let mut word = false;
+        let mut patterns = Vec::new();
+        let mut explicit_patterns = false;
+        let mut case = None;
+        let mut line_number = false;
+        let mut before_context = None;
+        let mut after_context = None;
+        let mut context = None;
+        let mut color = ColorChoice::Auto;
+        let mut invert = false;
+        let mut max_count = None;
+        let mut output = OutputMode::Lines;
+        let mut json = false;
+        let mut replace = None;
+        let mut in_place = false;
+        let mut fuzzy = None;
+        let mut decompress = false;
+        let mut interactive = false;
+        let mut index = false;
+        let mut threads = thread::available_parallelism().map_or(1, |n| n.get());
+        let mut positional = Vec::new();
+
+        let mut args = args.iter().skip(1);
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("option `{}` needs a value", arg))
+            };
+            let number = |value: &String| {
+                value
+                    .parse::<usize>()
+                    .map_err(|_| format!("option `{}` needs a number, got `{}`", arg, value))
+            };
+
+            match arg.as_str() {
+                "-E" | "--regex" => regex = true,
+                "-F" | "--fixed-strings" => regex = false,
+                "-w" | "--word-regexp" => word = true,
+                "-e" | "--pattern" => {
+                    patterns.push(value()?.clone());
+                    explicit_patterns = true;
+                }
+                "-f" | "--file" => {
+                    let path = value()?;
+                    let contents = fs::read_to_string(path)
+                        .map_err(|e| format!("could not read patterns from `{}`: {}", path, e))?;
+                    patterns.extend(contents.lines().map(String::from));
+                    explicit_patterns = true;
+                }
+                "-i" | "--ignore-case" => case = Some(CaseMode::Insensitive),
+                "-s" | "--case-sensitive" => case = Some(CaseMode::Sensitive),
+                "-S" | "--smart-case" => case = Some(CaseMode::Smart),
+                "-n" | "--line-number" => line_number = true,
+                "-A" | "--after-context" => after_context = Some(number(value()?)?),
+                "-B" | "--before-context" => before_context = Some(number(value()?)?),
+                "-C" | "--context" => context = Some(number(value()?)?),
+                "-v" | "--invert-match" => invert = true,
+                "-c" | "--count" => output = OutputMode::Count,
+                "-l" | "--files-with-matches" => output = OutputMode::FilesWithMatches,
+                "-L" | "--files-without-match" => output = OutputMode::FilesWithoutMatch,
+                "-m" | "--max-count" => max_count = Some(number(value()?)?),
+                "--json" => json = true,
+                "-r" | "--replace" => replace = Some(value()?.clone()),
+                "--in-place" => in_place = true,
+                "--fuzzy" => fuzzy = Some(number(value()?)?),
+                "-z" | "--search-zip" => decompress = true,
+                "--interactive" => interactive = true,
+                "--index" => index = true,
+                "-j" | "--threads" => {
+                    threads = number(value()?)?;
+                    if threads == 0 {
+                        return Err(String::from("need at least one thread"));
+                    }
+                }
+                "--color" => {
+                    color = match value()?.as_str() {
+                        "always" => ColorChoice::Always,
+                        "never" => ColorChoice::Never,
+                        "auto" => ColorChoice::Auto,
+                        other => return Err(format!("unknown --color value `{}`", other)),
+                    }
+                }
+                _ if arg.starts_with('-') && arg.len() > 1 => {
+                    return Err(format!("unknown option `{}`", arg))
+                }
+                _ => positional.push(arg),
+            }
         }
-        let query = args[1].clone();
-        let filename = args[2].clone();
 
-        let case_sensitive = env::var("CASE_INSENSITIVE").is_err();
-        
+        if !explicit_patterns {
+            if interactive && positional.len() == 1 {
+                // the query is typed in once the UI is up
+                patterns.push(String::new());
+            } else if let Some(query) = positional.first() {
+                patterns.push(query.to_string());
+                positional.remove(0);
+            }
+        }
+        if positional.is_empty() {
+            return Err(String::from("not enough arguments"));
+        }
+        if patterns.is_empty() {
+            return Err(String::from("no patterns given"));
+        }
+        if json && output != OutputMode::Lines {
+            return Err(String::from("--json cannot be combined with -c, -l or -L"));
+        }
+        let paths: Vec<String> = positional.iter().map(|p| p.to_string()).collect();
+
+        if in_place {
+            if replace.is_none() {
+                return Err(String::from("--in-place needs --replace"));
+            }
+            if invert || json || decompress || output != OutputMode::Lines {
+                return Err(String::from(
+                    "--in-place cannot be combined with -v, -c, -l, -L, -z or --json",
+                ));
+            }
+            if paths.iter().any(|path| path == input::STDIN) {
+                return Err(String::from("standard input cannot be rewritten in place"));
+            }
+        }
+
+        if interactive {
+            if paths.len() != 1 || paths[0] == input::STDIN || Path::new(&paths[0]).is_dir() {
+                return Err(String::from("--interactive searches a single file"));
+            }
+            if json
+                || in_place
+                || replace.is_some()
+                || fuzzy.is_some()
+                || output != OutputMode::Lines
+            {
+                return Err(String::from(
+                    "--interactive cannot be combined with -c, -l, -L, --json, --replace or --fuzzy",
+                ));
+            }
+        }
+
+        if fuzzy.is_some() {
+            if patterns.len() > 1 || regex || word {
+                return Err(String::from(
+                    "--fuzzy needs a single plain text pattern without -w",
+                ));
+            }
+            let has_context = before_context.or(after_context).or(context).is_some();
+            // --json has no spans to report, as a fuzzy match has no one place
+            if invert
+                || has_context
+                || max_count.is_some()
+                || replace.is_some()
+                || json
+                || output != OutputMode::Lines
+            {
+                return Err(String::from(
+                    "--fuzzy cannot be combined with -v, -A, -B, -C, -m, -c, -l, -L, --json or --replace",
+                ));
+            }
+        }
+
+        if index {
+            if paths.iter().any(|path| !Path::new(path).is_dir()) {
+                return Err(String::from("--index searches indexed directories"));
+            }
+            if decompress || fuzzy.is_some() || interactive {
+                return Err(String::from(
+                    "--index cannot be combined with -z, --fuzzy or --interactive",
+                ));
+            }
+        }
+
+        let case = case.unwrap_or(if env_case_insensitive {
+            CaseMode::Insensitive
+        } else {
+            CaseMode::Sensitive
+        });
 
-        Ok(Config { query, filename, case_sensitive })
+        Ok(Config {
+            patterns,
+            paths,
+            case,
+            regex,
+            word,
+            line_number,
+            // like grep, -A and -B win over -C whatever order they come in
+            before_context: before_context.or(context).unwrap_or(0),
+            after_context: after_context.or(context).unwrap_or(0),
+            color,
+            invert,
+            max_count,
+            output,
+            json,
+            replace,
+            in_place,
+            fuzzy,
+            decompress,
+            interactive,
+            index,
+            threads,
+        })
+    }
+
+    /// Whether the search should ignore case, resolving smart case against
+    /// the patterns.
+    pub fn ignore_case(&self) -> bool {
+        match self.case {
+            CaseMode::Sensitive => false,
+            CaseMode::Insensitive => true,
+            CaseMode::Smart => !self
+                .patterns
+                .iter()
+                .any(|pattern| pattern.chars().any(char::is_uppercase)),
+        }
     }
 }
 
@@ -50,12 +376,37 @@ pub fn search<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
     results
 }
 
+/// Lines containing `query` with at most `max_edits` characters inserted,
+/// deleted or changed, closest first, each with its distance. Lines the
+/// same distance away keep their order.
+pub fn search_fuzzy<'a>(query: &str, contents: &'a str, max_edits: usize) -> Vec<(usize, &'a str)> {
+    let mut results: Vec<(usize, &str)> = contents
+        .lines()
+        .map(|line| (fuzzy::substring_distance(query, line), line))
+        .filter(|&(distance, _)| distance <= max_edits)
+        .collect();
+    results.sort_by_key(|&(distance, _)| distance);
+    results
+}
+
+pub fn search_regex<'a>(regex: &Regex, contents: &'a str) -> Vec<&'a str> {
+    contents
+        .lines()
+        .filter(|line| regex.is_match(line))
+        .collect()
+}
+
 pub fn search_case_insensitive<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
     let mut results = Vec::new();
-    let query = query.to_lowercase();
+    let query = fold::fold(query);
 
     for line in contents.lines() {
-        if line.to_lowercase().contains(&query) {
+        let folded = fold::Folded::new(line);
+        let found = folded
+            .text
+            .match_indices(&query)
+            .any(|(start, m)| folded.original(start..start + m.len()).is_some());
+        if found {
             results.push(line);
         }
     }
@@ -77,13 +428,332 @@ Duct Tape.";
         assert_eq!(vec!["Safe, Fast, Productive."], search(query, contents));
     }
 
-        #[test]
+    #[test]
     fn case_insensitive() {
         let query = "rUsT";
-        let contents = "/
-Rust: 
+        let contents = "\
+Rust:
+Safe, Fast, Productive.
+Trust me.";
+        assert_eq!(
+            vec!["Rust:", "Trust me."],
+            search_case_insensitive(query, contents)
+        );
+    }
+
+    #[test]
+    fn case_insensitive_folds_case_fully() {
+        let contents = "\
+Die Straße
+STRASSE
+Strase";
+        assert_eq!(
+            vec!["Die Straße", "STRASSE"],
+            search_case_insensitive("strasse", contents)
+        );
+        assert_eq!(
+            vec!["Die Straße", "STRASSE"],
+            search_case_insensitive("STRAẞE", contents)
+        );
+        assert!(search_case_insensitive("stras", "Straße").is_empty());
+    }
+
+    #[test]
+    fn fuzzy() {
+        let contents = "\
+Rust:
+Safe, Fast, Productive.
+Pick three.
+Trust me.
+Rutsy";
+        assert_eq!(
+            vec![
+                (0, "Trust me."),
+                (1, "Rust:"),
+                (2, "Safe, Fast, Productive."),
+                (2, "Rutsy")
+            ],
+            search_fuzzy("rust", contents, 2)
+        );
+        assert_eq!(vec![(0, "Trust me.")], search_fuzzy("rust", contents, 0));
+        assert_eq!(vec![(1, "Pick three.")], search_fuzzy("thee", contents, 1));
+    }
+
+    #[test]
+    fn regex() {
+        let regex = Regex::new(r"^\w+,").unwrap();
+        let contents = "\
+Rust:
+Safe, Fast, Productive.
+Pick three.";
+        assert_eq!(
+            vec!["Safe, Fast, Productive."],
+            search_regex(&regex, contents)
+        );
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn matching_lines<'a>(config: &Config, contents: &'a str) -> Vec<&'a str> {
+        let matcher = Matcher::new(config).unwrap();
+        contents
+            .lines()
+            .filter(|line| matcher.is_match(line))
+            .collect()
+    }
+
+    #[test]
+    fn regex_flag() {
+        for flag in ["-E", "--regex"] {
+            let config = Config::new(&args(&["minigrep", flag, "f[a-z]+", "poem.txt"])).unwrap();
+            assert!(config.regex);
+            assert_eq!(config.patterns, vec!["f[a-z]+"]);
+            assert_eq!(config.paths, vec!["poem.txt"]);
+        }
+
+        // like grep, -e gives the pattern and does not turn on regexes
+        let config = Config::new(&args(&["minigrep", "-e", "f[a-z]+", "poem.txt"])).unwrap();
+        assert!(!config.regex);
+    }
+
+    #[test]
+    fn invalid_regex_is_an_error() {
+        let config =
+            Config::parse(&args(&["minigrep", "-E", "(unclosed", "poem.txt"]), false).unwrap();
+        assert!(run(config).is_err());
+    }
+
+    #[test]
+    fn case_sensitive_by_default() {
+        let config = Config::parse(&args(&["minigrep", "rUsT", "poem.txt"]), false).unwrap();
+        assert_eq!(config.case, CaseMode::Sensitive);
+
+        let contents = "\
+Rust:
+Trust me.";
+        assert!(matching_lines(&config, contents).is_empty());
+    }
+
+    #[test]
+    fn env_var_makes_search_case_insensitive() {
+        let config = Config::parse(&args(&["minigrep", "rUsT", "poem.txt"]), true).unwrap();
+        assert_eq!(config.case, CaseMode::Insensitive);
+
+        let contents = "\
+Rust:
+Trust me.";
+        assert_eq!(
+            vec!["Rust:", "Trust me."],
+            matching_lines(&config, contents)
+        );
+    }
+
+    #[test]
+    fn case_flags_override_env_var() {
+        let config = Config::parse(&args(&["minigrep", "-i", "rust", "poem.txt"]), false).unwrap();
+        assert_eq!(config.case, CaseMode::Insensitive);
+
+        let config = Config::parse(
+            &args(&["minigrep", "--case-sensitive", "rust", "poem.txt"]),
+            true,
+        )
+        .unwrap();
+        assert_eq!(config.case, CaseMode::Sensitive);
+
+        // the last flag wins
+        let config =
+            Config::parse(&args(&["minigrep", "-i", "rust", "poem.txt", "-s"]), true).unwrap();
+        assert_eq!(config.case, CaseMode::Sensitive);
+    }
+
+    #[test]
+    fn smart_case() {
+        let config = Config::parse(
+            &args(&["minigrep", "--smart-case", "rust", "poem.txt"]),
+            false,
+        )
+        .unwrap();
+        assert!(config.ignore_case());
+
+        let config = Config::parse(&args(&["minigrep", "-S", "Rust", "poem.txt"]), true).unwrap();
+        assert!(!config.ignore_case());
+    }
+
+    #[test]
+    fn case_insensitive_regex() {
+        let config =
+            Config::parse(&args(&["minigrep", "-E", "-i", "^T", "poem.txt"]), false).unwrap();
+        let contents = "\
+Rust:
+trust me.";
+        assert_eq!(vec!["trust me."], matching_lines(&config, contents));
+    }
+
+    #[test]
+    fn several_paths() {
+        let config = Config::new(&args(&["minigrep", "rust", "src", "poem.txt"])).unwrap();
+        assert_eq!(config.patterns, vec!["rust"]);
+        assert_eq!(config.paths, vec!["src", "poem.txt"]);
+    }
+
+    #[test]
+    fn several_patterns() {
+        let config = Config::new(&args(&[
+            "minigrep",
+            "-e",
+            "rust",
+            "-w",
+            "--pattern",
+            "-n",
+            "src",
+            "poem.txt",
+        ]))
+        .unwrap();
+        assert_eq!(config.patterns, vec!["rust", "-n"]);
+        assert_eq!(config.paths, vec!["src", "poem.txt"]);
+        assert!(config.word);
+        assert!(!config.line_number);
+
+        // -F undoes an earlier -E
+        let config = Config::new(&args(&["minigrep", "-E", "-F", "a.b", "."])).unwrap();
+        assert!(!config.regex);
+
+        assert!(Config::new(&args(&["minigrep", "-e", "rust"])).is_err());
+    }
+
+    #[test]
+    fn patterns_from_a_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("patterns.txt");
+        fs::write(&file, "Rust\ntrust\n").unwrap();
+        let file = file.display().to_string();
+
+        let config = Config::new(&args(&["minigrep", "-e", "safe", "-f", &file, "."])).unwrap();
+        assert_eq!(config.patterns, vec!["safe", "Rust", "trust"]);
+
+        let contents = "\
+Rust:
 Safe, Fast, Productive.
 Trust me.";
-        assert_eq!(vec!["Rust:", "Trust me."], search_case_insensitive(query, contents));
+        assert_eq!(vec!["Rust:"], matching_lines(&config, contents));
+
+        let missing = dir.path().join("missing").display().to_string();
+        assert!(Config::new(&args(&["minigrep", "-f", &missing, "."])).is_err());
+    }
+
+    #[test]
+    fn context_options() {
+        let config = Config::new(&args(&[
+            "minigrep", "-n", "-C", "2", "-A", "1", "rust", ".",
+        ]))
+        .unwrap();
+        assert!(config.line_number);
+        assert_eq!(config.before_context, 2);
+        assert_eq!(config.after_context, 1);
+
+        assert!(Config::new(&args(&["minigrep", "-A", "lots", "rust", "."])).is_err());
+        assert!(Config::new(&args(&["minigrep", "rust", ".", "-B"])).is_err());
+    }
+
+    #[test]
+    fn output_modes() {
+        let config = Config::new(&args(&["minigrep", "-v", "-m", "3", "rust", "."])).unwrap();
+        assert!(config.invert);
+        assert_eq!(config.max_count, Some(3));
+        assert_eq!(config.output, OutputMode::Lines);
+
+        // the last of -c, -l and -L wins
+        let config = Config::new(&args(&["minigrep", "-l", "-c", "rust", "."])).unwrap();
+        assert_eq!(config.output, OutputMode::Count);
+        let config = Config::new(&args(&["minigrep", "-c", "-L", "rust", "."])).unwrap();
+        assert_eq!(config.output, OutputMode::FilesWithoutMatch);
+    }
+
+    #[test]
+    fn json_option() {
+        let config = Config::new(&args(&["minigrep", "--json", "-n", "rust", "."])).unwrap();
+        assert!(config.json);
+
+        assert!(Config::new(&args(&["minigrep", "--json", "-l", "rust", "."])).is_err());
+    }
+
+    #[test]
+    fn interactive_option() {
+        let config = Config::new(&args(&["minigrep", "--interactive", "poem.txt"])).unwrap();
+        assert!(config.interactive);
+        assert_eq!(config.patterns, vec![""]);
+        assert_eq!(config.paths, vec!["poem.txt"]);
+
+        let config =
+            Config::new(&args(&["minigrep", "--interactive", "rust", "poem.txt"])).unwrap();
+        assert_eq!(config.patterns, vec!["rust"]);
+
+        assert!(Config::new(&args(&["minigrep", "--interactive", "rust", "a", "b"])).is_err());
+        assert!(Config::new(&args(&["minigrep", "--interactive", "-"])).is_err());
+        assert!(Config::new(&args(&["minigrep", "--interactive", "-c", "poem.txt"])).is_err());
+    }
+
+    #[test]
+    fn fuzzy_option() {
+        let config = Config::new(&args(&["minigrep", "--fuzzy", "2", "rust", "."])).unwrap();
+        assert_eq!(config.fuzzy, Some(2));
+
+        assert!(Config::new(&args(&["minigrep", "--fuzzy", "-1", "rust", "."])).is_err());
+        assert!(Config::new(&args(&["minigrep", "--fuzzy", "1", "-E", "r.st", "."])).is_err());
+        assert!(Config::new(&args(&["minigrep", "--fuzzy", "1", "-C", "2", "rust", "."])).is_err());
+        assert!(Config::new(&args(&["minigrep", "--fuzzy", "1", "--json", "rust", "."])).is_err());
+    }
+
+    #[test]
+    fn index_option() {
+        let config = Config::new(&args(&["minigrep", "--index", "rust", "."])).unwrap();
+        assert!(config.index);
+
+        assert!(Config::new(&args(&["minigrep", "--index", "rust", "poem.txt"])).is_err());
+        assert!(Config::new(&args(&["minigrep", "--index", "rust", "-"])).is_err());
+        assert!(Config::new(&args(&["minigrep", "--index", "-z", "rust", "."])).is_err());
+    }
+
+    #[test]
+    fn replace_options() {
+        let config = Config::new(&args(&["minigrep", "-r", "$1", "rust", "."])).unwrap();
+        assert_eq!(config.replace.as_deref(), Some("$1"));
+        assert!(!config.in_place);
+
+        let config = Config::new(&args(&[
+            "minigrep",
+            "--in-place",
+            "--replace",
+            "x",
+            "rust",
+            ".",
+        ]))
+        .unwrap();
+        assert!(config.in_place);
+
+        assert!(Config::new(&args(&["minigrep", "--in-place", "rust", "."])).is_err());
+        assert!(Config::new(&args(&[
+            "minigrep",
+            "--in-place",
+            "-r",
+            "x",
+            "-z",
+            "rust",
+            "."
+        ]))
+        .is_err());
+        assert!(Config::new(&args(&[
+            "minigrep",
+            "--in-place",
+            "-r",
+            "x",
+            "-c",
+            "rust",
+            "."
+        ]))
+        .is_err());
+        assert!(Config::new(&args(&["minigrep", "--in-place", "-r", "x", "rust", "-"])).is_err());
     }
 }
@@ -1,20 +1,63 @@
 use minigrep::Config;
 use std::env;
+use std::path::Path;
 use std::process;
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let mut args: Vec<String> = env::args().collect();
+
+    match args.get(1).map(String::as_str) {
+        Some("index") => return index(&args[2..]),
+        // `search` is optional, but lets a search for the word "index" be
+        // written as `minigrep search index PATH`
+        Some("search") => {
+            args.remove(1);
+        }
+        _ => {}
+    }
 
     let config = Config::new(&args).unwrap_or_else(|err| {
         eprintln!("Problem parsing arguments: {}", err);
         process::exit(1);
     });
 
-    println!("searching for {}", config.query);
-    println!("in file {}", config.filename);
+    // on stderr, so piping the results (say `minigrep -l foo dir | xargs ...`)
+    // only passes on the results; the terminal UI draws its own screen
+    if !config.interactive {
+        eprintln!("searching for {}", config.patterns.join(", "));
+        eprintln!("in {}", config.paths.join(", "));
+    }
 
     if let Err(e) = minigrep::run(config) {
         eprintln!("Application error: {}", e);
         process::exit(1);
     }
 }
+
+fn index(args: &[String]) {
+    let [dir] = args else {
+        eprintln!("Problem parsing arguments: usage: minigrep index DIR");
+        process::exit(1);
+    };
+
+    match minigrep::index::build(Path::new(dir)) {
+        Ok(update) => {
+            println!(
+                "indexed {} file(s), {} unchanged, {} removed",
+                update.indexed, update.unchanged, update.removed
+            );
+            // the files are left out of the index, so say so like a search would
+            if update.errors > 0 {
+                eprintln!(
+                    "Application error: {} file(s) could not be indexed",
+                    update.errors
+                );
+                process::exit(1);
+            }
+        }
+        Err(e) => {
+            eprintln!("Application error: {}", e);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,294 @@
+use crate::fold::{self, Folded};
+use crate::Config;
+use aho_corasick::{AhoCorasick, MatchKind};
+use regex::{Regex, RegexBuilder};
+use std::cmp::Reverse;
+use std::error::Error;
+use std::ops::Range;
+
+/// Finds the patterns inside a single line, reporting where each match
+/// starts and ends so it can be highlighted.
+pub struct Matcher {
+    engine: Engine,
+    /// Only accept matches with no word character on either side.
+    word: bool,
+    /// Whether `$1` in a replacement refers to a capture group, which only
+    /// makes sense for regular expressions.
+    expand: bool,
+}
+
+enum Engine {
+    /// Plain, case sensitive text. Aho-Corasick looks for every pattern in a
+    /// single pass, so hundreds of them cost little more than one.
+    Literal(AhoCorasick),
+    /// Case insensitive plain text. The patterns are case folded up front and
+    /// each line is folded before it is searched, with the matches mapped
+    /// back onto the original line.
+    Folded(AhoCorasick),
+    /// Regular expressions, one alternative per pattern. These use the regex
+    /// crate's simple case folding when ignoring case.
+    Regex(Regex),
+}
+
+impl Matcher {
+    pub fn new(config: &Config) -> Result<Matcher, Box<dyn Error>> {
+        let engine = if !config.regex && !config.ignore_case() {
+            // whole words need every candidate, not just the leftmost ones
+            let kind = if config.word {
+                MatchKind::Standard
+            } else {
+                MatchKind::LeftmostLongest
+            };
+            let automaton = AhoCorasick::builder()
+                .match_kind(kind)
+                .build(&config.patterns)?;
+            Engine::Literal(automaton)
+        } else if !config.regex {
+            // a match that only covers part of a character's folding is
+            // thrown away, so this needs every candidate too
+            let patterns: Vec<String> = config.patterns.iter().map(|p| fold::fold(p)).collect();
+            let automaton = AhoCorasick::builder()
+                .match_kind(MatchKind::Standard)
+                .build(&patterns)?;
+            Engine::Folded(automaton)
+        } else {
+            let alternatives: Vec<String> = config
+                .patterns
+                .iter()
+                .map(|pattern| format!("(?:{})", pattern))
+                .collect();
+            let regex = RegexBuilder::new(&alternatives.join("|"))
+                .case_insensitive(config.ignore_case())
+                .build()?;
+            Engine::Regex(regex)
+        };
+
+        Ok(Matcher {
+            engine,
+            word: config.word,
+            expand: config.regex,
+        })
+    }
+
+    pub fn is_match(&self, line: &str) -> bool {
+        match (&self.engine, self.word) {
+            (Engine::Literal(automaton), false) => automaton.is_match(line),
+            (Engine::Regex(regex), false) => regex.is_match(line),
+            _ => !self.find_iter(line).is_empty(),
+        }
+    }
+
+    /// Byte ranges of every non-overlapping match in `line`.
+    pub fn find_iter(&self, line: &str) -> Vec<Range<usize>> {
+        match &self.engine {
+            Engine::Literal(automaton) if self.word => leftmost_longest(
+                automaton
+                    .find_overlapping_iter(line)
+                    .map(|m| m.range())
+                    .filter(|span| is_whole_word(line, span)),
+            ),
+            Engine::Literal(automaton) => automaton.find_iter(line).map(|m| m.range()).collect(),
+            Engine::Folded(automaton) => {
+                let folded = Folded::new(line);
+                leftmost_longest(
+                    automaton
+                        .find_overlapping_iter(&folded.text)
+                        .filter_map(|m| folded.original(m.range()))
+                        .filter(|span| !self.word || is_whole_word(line, span)),
+                )
+            }
+            Engine::Regex(regex) if self.word => {
+                let mut words = Vec::new();
+                let mut start = 0;
+                while let Some(m) = regex.find_at(line, start) {
+                    let span = m.range();
+                    if is_whole_word(line, &span) {
+                        start = if span.is_empty() {
+                            next_char(line, span.end)
+                        } else {
+                            span.end
+                        };
+                        words.push(span);
+                    } else {
+                        start = next_char(line, span.start);
+                    }
+                    if start > line.len() {
+                        break;
+                    }
+                }
+                words
+            }
+            Engine::Regex(regex) => regex.find_iter(line).map(|m| m.range()).collect(),
+        }
+    }
+
+    /// `line` with every match replaced by `replacement`, along with where
+    /// the replacements ended up. In regex mode `$1` or `${name}` in
+    /// `replacement` is expanded to the text of that capture group.
+    pub fn replace(&self, line: &str, replacement: &str) -> (String, Vec<Range<usize>>) {
+        let mut text = String::with_capacity(line.len());
+        let mut spans = Vec::new();
+        let mut end = 0;
+
+        for span in self.find_iter(line) {
+            text.push_str(&line[end..span.start]);
+            let start = text.len();
+            match &self.engine {
+                Engine::Regex(regex) if self.expand => {
+                    // the leftmost match from here is the one just found
+                    if let Some(captures) = regex.captures_at(line, span.start) {
+                        captures.expand(replacement, &mut text);
+                    }
+                }
+                _ => text.push_str(replacement),
+            }
+            spans.push(start..text.len());
+            end = span.end;
+        }
+
+        text.push_str(&line[end..]);
+        (text, spans)
+    }
+}
+
+/// Picks the leftmost, then longest, of any candidate matches that overlap.
+fn leftmost_longest(candidates: impl Iterator<Item = Range<usize>>) -> Vec<Range<usize>> {
+    let mut spans: Vec<Range<usize>> = candidates.collect();
+    spans.sort_by_key(|span| (span.start, Reverse(span.end)));
+    let mut end = None;
+    spans.retain(|span| {
+        let keep = end.is_none_or(|end| span.start >= end && span.end > end);
+        if keep {
+            end = Some(span.end);
+        }
+        keep
+    });
+    spans
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Whether `span` has no word character directly before or after it.
+fn is_whole_word(line: &str, span: &Range<usize>) -> bool {
+    let before = line[..span.start].chars().next_back();
+    let after = line[span.end..].chars().next();
+    !before.is_some_and(is_word_char) && !after.is_some_and(is_word_char)
+}
+
+/// The byte index just past the character starting at `index`.
+fn next_char(line: &str, index: usize) -> usize {
+    index + line[index..].chars().next().map_or(1, char::len_utf8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CaseMode;
+
+    fn config(patterns: &[&str], case: CaseMode, regex: bool) -> Config {
+        let mut config = Config::new(&["minigrep".into(), "x".into(), ".".into()]).unwrap();
+        config.patterns = patterns.iter().map(|p| p.to_string()).collect();
+        config.case = case;
+        config.regex = regex;
+        config
+    }
+
+    fn word_config(patterns: &[&str], regex: bool) -> Config {
+        let mut config = config(patterns, CaseMode::Sensitive, regex);
+        config.word = true;
+        config
+    }
+
+    #[test]
+    fn literal_spans() {
+        let matcher = Matcher::new(&config(&["ab"], CaseMode::Sensitive, false)).unwrap();
+        assert_eq!(matcher.find_iter("ab-AB-ab"), vec![0..2, 6..8]);
+    }
+
+    #[test]
+    fn case_insensitive_literal_is_not_a_regex() {
+        let matcher = Matcher::new(&config(&["a.b"], CaseMode::Insensitive, false)).unwrap();
+        assert!(matcher.is_match("x A.B y"));
+        assert!(!matcher.is_match("axb"));
+        assert_eq!(matcher.find_iter("x A.B y"), vec![2..5]);
+    }
+
+    #[test]
+    fn full_case_folding() {
+        let matcher = Matcher::new(&config(&["STRASSE"], CaseMode::Insensitive, false)).unwrap();
+        // the spans are in the original line, although ß folds to two bytes
+        assert_eq!(
+            matcher.find_iter("Die Straße, die strasse"),
+            vec![4..11, 17..24]
+        );
+
+        let matcher = Matcher::new(&config(&["ß"], CaseMode::Insensitive, false)).unwrap();
+        assert_eq!(matcher.find_iter("MASSE Maße"), vec![2..4, 8..10]);
+
+        // half of a folded ß is not a match for s
+        let matcher = Matcher::new(&config(&["s"], CaseMode::Insensitive, false)).unwrap();
+        assert!(!matcher.is_match("ß"));
+
+        // İ folds to i followed by a combining dot
+        let matcher = Matcher::new(&config(&["i̇stanbul"], CaseMode::Insensitive, false)).unwrap();
+        assert_eq!(matcher.find_iter("İSTANBUL"), vec![0..9]);
+    }
+
+    #[test]
+    fn regex_spans() {
+        let matcher = Matcher::new(&config(&["[0-9]+"], CaseMode::Sensitive, true)).unwrap();
+        assert_eq!(matcher.find_iter("a1 b22 c"), vec![1..2, 4..6]);
+    }
+
+    #[test]
+    fn several_literals_prefer_the_longest() {
+        let matcher = Matcher::new(&config(
+            &["foo", "foobar", "baz"],
+            CaseMode::Sensitive,
+            false,
+        ))
+        .unwrap();
+        assert_eq!(matcher.find_iter("foobar baz fo"), vec![0..6, 7..10]);
+        assert!(!matcher.is_match("fob"));
+    }
+
+    #[test]
+    fn several_regexes() {
+        let matcher = Matcher::new(&config(&["^a", "b$"], CaseMode::Sensitive, true)).unwrap();
+        assert_eq!(matcher.find_iter("ab b"), vec![0..1, 3..4]);
+        assert!(!matcher.is_match("ba"));
+    }
+
+    #[test]
+    fn whole_words() {
+        let matcher = Matcher::new(&word_config(&["foo", "foo bar"], false)).unwrap();
+        assert_eq!(matcher.find_iter("foo barx foo_ (foo)"), vec![0..3, 15..18]);
+        assert!(!matcher.is_match("food"));
+
+        let matcher = Matcher::new(&word_config(&["-x"], false)).unwrap();
+        assert_eq!(matcher.find_iter("a -x b-x"), vec![2..4]);
+
+        let matcher = Matcher::new(&word_config(&["fo+"], true)).unwrap();
+        assert_eq!(matcher.find_iter("foo fooo: xfoo föo"), vec![0..3, 4..8]);
+        assert!(!matcher.is_match("foos"));
+    }
+
+    #[test]
+    fn replacements() {
+        let matcher =
+            Matcher::new(&config(&[r"(?P<key>\w+)=(\d+)"], CaseMode::Sensitive, true)).unwrap();
+        assert_eq!(
+            matcher.replace("a=1, bb=22", "${key}:$2"),
+            (String::from("a:1, bb:22"), vec![0..3, 5..10])
+        );
+
+        // plain text replacements are used as they are
+        let matcher = Matcher::new(&config(&["a.b"], CaseMode::Insensitive, false)).unwrap();
+        assert_eq!(
+            matcher.replace("A.B c a.b", "$1"),
+            (String::from("$1 c $1"), vec![0..2, 5..7])
+        );
+    }
+}
@@ -0,0 +1,497 @@
+use crate::decompress;
+use crate::input;
+use crate::matcher::Matcher;
+use crate::printer::Printer;
+use crate::{rewrite, walk};
+use serde::Serialize;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Output is handed from a worker to the writer in pieces of about this
+/// many bytes.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// How many pieces of output a job can have waiting before its worker has
+/// to wait for the writer. Together with the jobs in flight this bounds the
+/// memory used however large the files are.
+const CHUNKS_AHEAD: usize = 4;
+
+/// One unit of work for the pool, numbered in the order the paths were
+/// walked so results can be put back into that order.
+enum Job {
+    Stdin,
+    File(PathBuf),
+    /// A path the walker could not read, reported in its place in the output.
+    Failed(String),
+}
+
+/// How many lines a searched job selected, or `None` if it was skipped as
+/// binary, or the error to report instead.
+type Outcome = Result<Option<usize>, String>;
+
+/// What a worker sends the writer about the job it is searching.
+enum Message {
+    /// More output. `flush` asks for it to be passed on straight away,
+    /// for input such as a pipe that may be slow to arrive.
+    Output { bytes: Vec<u8>, flush: bool },
+    /// The job is finished.
+    Done(Outcome),
+}
+
+/// Collects a job's output and sends it to the writer a chunk at a time, or
+/// a write at a time when `live`.
+struct ChunkWriter {
+    buffer: Vec<u8>,
+    tx: SyncSender<Message>,
+    live: bool,
+}
+
+impl ChunkWriter {
+    fn send(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let message = Message::Output {
+            bytes: mem::take(&mut self.buffer),
+            flush: self.live,
+        };
+        self.tx
+            .send(message)
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "output was closed"))
+    }
+}
+
+impl Write for ChunkWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        if self.live || self.buffer.len() >= CHUNK_SIZE {
+            self.send()?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.send()
+    }
+}
+
+/// Totals over everything `search_paths` looked at.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Stats {
+    pub files_searched: usize,
+    pub files_with_matches: usize,
+    pub matched_lines: usize,
+    /// Paths that could not be searched.
+    pub errors: usize,
+}
+
+/// Searches every file under `paths` on `threads` worker threads and writes
+/// the results to `out`, in the same order a single thread would have
+/// produced. The file that is next in order is written as it is searched;
+/// files that finish ahead of it wait in a small bounded buffer each.
+pub fn search_paths(
+    paths: &[String],
+    threads: usize,
+    matcher: &Matcher,
+    printer: &mut Printer,
+    out: &mut impl Write,
+) -> io::Result<Stats> {
+    search_jobs(|| jobs(paths), threads, matcher, printer, out)
+}
+
+/// Like `search_paths`, but searches exactly the files given, in the order
+/// given, without walking anything.
+pub fn search_files(
+    files: &[PathBuf],
+    threads: usize,
+    matcher: &Matcher,
+    printer: &mut Printer,
+    out: &mut impl Write,
+) -> io::Result<Stats> {
+    search_jobs(
+        || files.iter().cloned().map(Job::File),
+        threads,
+        matcher,
+        printer,
+        out,
+    )
+}
+
+/// Runs the jobs `make_jobs` produces. The iterator is made on the thread
+/// that feeds the workers, so walking happens alongside searching.
+fn search_jobs<'a, I: Iterator<Item = Job> + 'a>(
+    make_jobs: impl FnOnce() -> I + Send + 'a,
+    threads: usize,
+    matcher: &Matcher,
+    printer: &mut Printer,
+    out: &mut impl Write,
+) -> io::Result<Stats> {
+    let threads = threads.max(1);
+    // every job gets its own channel for its output, and the writer is
+    // handed the receiving ends in walk order; both queues are bounded so
+    // the walk cannot run far ahead of the writer
+    let (job_tx, job_rx) = mpsc::sync_channel::<(Job, SyncSender<Message>)>(threads);
+    // shared by the workers only, so if they all stop early the walker's
+    // next send fails instead of waiting forever
+    let job_rx = Arc::new(Mutex::new(job_rx));
+    let (order_tx, order_rx) = mpsc::sync_channel::<Receiver<Message>>(threads * 2);
+    let template = printer.fresh();
+    let walk_first = template.in_place.is_some();
+
+    thread::scope(|scope| {
+        scope.spawn(move || {
+            let mut jobs: Box<dyn Iterator<Item = Job>> = Box::new(make_jobs());
+            if walk_first {
+                // so the backups written along the way are not rewritten too
+                jobs = Box::new(jobs.collect::<Vec<_>>().into_iter());
+            }
+            for job in jobs {
+                let (tx, rx) = mpsc::sync_channel(CHUNKS_AHEAD);
+                // the writer has to know about a job before a worker can
+                // start it, so it is never left waiting on one no worker has
+                if order_tx.send(rx).is_err() || job_tx.send((job, tx)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        for _ in 0..threads {
+            let (job_rx, template) = (Arc::clone(&job_rx), &template);
+            scope.spawn(move || loop {
+                // hold the lock only while taking the next job
+                let next = job_rx.lock().unwrap().recv();
+                let Ok((job, tx)) = next else { break };
+                let outcome = search_job(job, template, matcher, tx.clone());
+                if tx.send(Message::Done(outcome)).is_err() {
+                    // the writer has stopped
+                    break;
+                }
+            });
+        }
+        drop(job_rx);
+
+        let mut stats = Stats::default();
+        for rx in order_rx {
+            let mut started = false;
+            for message in rx {
+                match message {
+                    Message::Output { bytes, flush } => {
+                        if started {
+                            out.write_all(&bytes)?;
+                        } else {
+                            // the first output of a file may need a
+                            // separator from the file before it
+                            printer.write_output(out, &bytes)?;
+                            started = true;
+                        }
+                        if flush {
+                            out.flush()?;
+                        }
+                    }
+                    Message::Done(Ok(Some(matches))) => {
+                        stats.files_searched += 1;
+                        stats.files_with_matches += (matches > 0) as usize;
+                        stats.matched_lines += matches;
+                    }
+                    Message::Done(Ok(None)) => {}
+                    Message::Done(Err(message)) => {
+                        eprintln!("minigrep: {}", message);
+                        stats.errors += 1;
+                    }
+                }
+            }
+        }
+
+        Ok(stats)
+    })
+}
+
+fn jobs(paths: &[String]) -> impl Iterator<Item = Job> + '_ {
+    paths
+        .iter()
+        .flat_map(|path| -> Box<dyn Iterator<Item = Job>> {
+            if path == input::STDIN {
+                Box::new(std::iter::once(Job::Stdin))
+            } else {
+                Box::new(walk::walk(path).map(|entry| match entry {
+                    Ok(path) => Job::File(path),
+                    Err(e) => Job::Failed(e.to_string()),
+                }))
+            }
+        })
+}
+
+fn search_job(job: Job, template: &Printer, matcher: &Matcher, tx: SyncSender<Message>) -> Outcome {
+    let mut printer = template.fresh();
+    let mut output = ChunkWriter {
+        buffer: Vec::new(),
+        tx,
+        live: false,
+    };
+
+    let result = match job {
+        Job::Stdin => {
+            output.live = true;
+            search_reader(
+                &mut printer,
+                &mut output,
+                Path::new(input::STDIN_NAME),
+                io::stdin().lock(),
+                matcher,
+            )
+        }
+        Job::File(path) if printer.in_place.is_some() => {
+            let replacement = printer.in_place.as_deref().unwrap_or_default();
+            rewrite::rewrite_file(&path, matcher, replacement)
+                .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))
+        }
+        Job::File(path) => File::open(&path)
+            .and_then(|file| {
+                // pipes and devices may be written to slowly, so pass their
+                // results on line by line
+                output.live = !file.metadata()?.is_file();
+                search_reader(
+                    &mut printer,
+                    &mut output,
+                    &path,
+                    BufReader::new(file),
+                    matcher,
+                )
+            })
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e))),
+        Job::Failed(message) => return Err(message),
+    };
+
+    result
+        .and_then(|matches| output.flush().map(|_| matches))
+        .map_err(|e| e.to_string())
+}
+
+/// Searches one input, decompressing it first with `-z`.
+fn search_reader(
+    printer: &mut Printer,
+    out: &mut impl Write,
+    path: &Path,
+    reader: impl BufRead,
+    matcher: &Matcher,
+) -> io::Result<Option<usize>> {
+    if printer.decompress {
+        let reader = decompress::decoder(reader)?;
+        search_text(printer, out, path, reader, matcher)
+    } else {
+        search_text(printer, out, path, reader, matcher)
+    }
+}
+
+/// Searches one input, skipping it if it looks binary.
+fn search_text(
+    printer: &mut Printer,
+    out: &mut impl Write,
+    path: &Path,
+    mut reader: impl BufRead,
+    matcher: &Matcher,
+) -> io::Result<Option<usize>> {
+    if walk::is_binary(reader.fill_buf()?) {
+        return Ok(None);
+    }
+    printer.print_file(out, path, reader, matcher).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+    use std::fs;
+
+    fn search(dir: &Path, threads: usize) -> (String, Stats) {
+        search_with(&[], dir, threads)
+    }
+
+    fn search_with(options: &[&str], dir: &Path, threads: usize) -> (String, Stats) {
+        let args: Vec<String> = ["minigrep", "-n", "-C", "1"]
+            .iter()
+            .chain(options)
+            .chain(&["needle"])
+            .map(|s| s.to_string())
+            .chain(Some(dir.display().to_string()))
+            .collect();
+        let config = Config::new(&args).unwrap();
+        let matcher = Matcher::new(&config).unwrap();
+        let mut printer = Printer::new(&config, true);
+
+        let mut out = Vec::new();
+        let stats = search_paths(&config.paths, threads, &matcher, &mut printer, &mut out).unwrap();
+        (String::from_utf8(out).unwrap(), stats)
+    }
+
+    #[test]
+    fn output_order_does_not_depend_on_thread_count() {
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..40 {
+            // vary the file sizes so workers finish out of order
+            let filler = "hay\n".repeat((40 - i) * 50);
+            let contents = format!("{}needle {}\n{}", filler, i, filler);
+            fs::write(dir.path().join(format!("{:02}.txt", i)), contents).unwrap();
+        }
+        fs::write(dir.path().join("binary.bin"), b"needle\0").unwrap();
+
+        let (single, stats) = search(dir.path(), 1);
+        assert_eq!(
+            stats,
+            Stats {
+                files_searched: 40,
+                files_with_matches: 40,
+                matched_lines: 40,
+                errors: 0,
+            }
+        );
+        assert_eq!(single.lines().filter(|l| l.contains("needle")).count(), 40);
+        assert!(single.lines().next().unwrap().ends_with("00.txt-2000-hay"));
+        assert!(!single.contains("binary.bin"));
+
+        for _ in 0..5 {
+            assert_eq!(search(dir.path(), 8), (single.clone(), stats));
+        }
+    }
+
+    #[test]
+    fn searches_compressed_files_with_z() {
+        use flate2::write::GzEncoder;
+
+        let dir = tempfile::tempdir().unwrap();
+        // named .txt, so only the magic bytes give it away
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(b"hay\nneedle\nhay\n").unwrap();
+        fs::write(dir.path().join("log.txt"), encoder.finish().unwrap()).unwrap();
+
+        // without -z it is skipped as binary
+        let (output, stats) = search(dir.path(), 1);
+        assert_eq!(output, "");
+        assert_eq!(stats.files_searched, 0);
+
+        let (output, stats) = search_with(&["-z"], dir.path(), 1);
+        assert!(output.contains("log.txt:2:needle"));
+        assert_eq!(stats.matched_lines, 1);
+    }
+
+    /// Records the size of every write it is given.
+    #[derive(Default)]
+    struct Writes(Vec<usize>);
+
+    impl Write for Writes {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.push(buf.len());
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn large_files_are_not_buffered_whole() {
+        let dir = tempfile::tempdir().unwrap();
+        let line = "needle on every line\n";
+        let size = CHUNK_SIZE * CHUNKS_AHEAD * 8;
+        fs::write(dir.path().join("big.txt"), line.repeat(size / line.len())).unwrap();
+
+        let args = ["minigrep", "needle", &dir.path().display().to_string()].map(String::from);
+        let config = Config::new(&args).unwrap();
+        let matcher = Matcher::new(&config).unwrap();
+        let mut printer = Printer::new(&config, false);
+        let mut out = Writes::default();
+        let stats = search_paths(&config.paths, 4, &matcher, &mut printer, &mut out).unwrap();
+
+        assert_eq!(stats.matched_lines, size / line.len());
+        // the output arrives in pieces no bigger than a chunk and a line
+        assert!(out.0.len() > CHUNKS_AHEAD * 4);
+        assert!(out.0.iter().all(|&len| len < CHUNK_SIZE + line.len()));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn pipes_are_passed_on_as_they_arrive() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::Arc;
+        use std::time::{Duration, Instant};
+
+        let dir = tempfile::tempdir().unwrap();
+        let fifo = dir.path().join("fifo");
+        let made = std::process::Command::new("mkfifo").arg(&fifo).status();
+        if !made.is_ok_and(|status| status.success()) {
+            eprintln!("skipping: mkfifo is not available");
+            return;
+        }
+
+        /// Shares what was written so far with the test.
+        #[derive(Clone, Default)]
+        struct Shared(Arc<Mutex<Vec<u8>>>);
+
+        impl Write for Shared {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.lock().unwrap().extend_from_slice(buf);
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let out = Shared::default();
+        let seen_first = Arc::new(AtomicBool::new(false));
+        let writer = {
+            let (fifo, out, seen_first) = (fifo.clone(), out.clone(), Arc::clone(&seen_first));
+            thread::spawn(move || {
+                let mut pipe = fs::OpenOptions::new().write(true).open(&fifo).unwrap();
+                pipe.write_all(b"needle one\n").unwrap();
+                // hold the pipe open until the first line has come out
+                let start = Instant::now();
+                while start.elapsed() < Duration::from_secs(10) {
+                    if String::from_utf8_lossy(&out.0.lock().unwrap()).contains("needle one") {
+                        seen_first.store(true, Ordering::SeqCst);
+                        break;
+                    }
+                    thread::sleep(Duration::from_millis(10));
+                }
+                pipe.write_all(b"needle two\n").unwrap();
+            })
+        };
+
+        let args = ["minigrep", "needle", &fifo.display().to_string()].map(String::from);
+        let config = Config::new(&args).unwrap();
+        let matcher = Matcher::new(&config).unwrap();
+        let mut printer = Printer::new(&config, false);
+        let mut sink = out.clone();
+        search_files(
+            std::slice::from_ref(&fifo),
+            2,
+            &matcher,
+            &mut printer,
+            &mut sink,
+        )
+        .unwrap();
+        writer.join().unwrap();
+
+        assert!(seen_first.load(Ordering::SeqCst));
+        assert_eq!(
+            out.0.lock().unwrap().as_slice(),
+            b"needle one\nneedle two\n"
+        );
+    }
+
+    #[test]
+    fn failures_are_counted() {
+        let dir = tempfile::tempdir().unwrap();
+        let (output, stats) = search(&dir.path().join("missing"), 4);
+        assert_eq!(output, "");
+        assert_eq!(stats.errors, 1);
+        assert_eq!(stats.files_searched, 0);
+    }
+}
@@ -0,0 +1,535 @@
+use crate::input;
+use crate::matcher::Matcher;
+use crate::pool::Stats;
+use crate::{ColorChoice, Config, OutputMode};
+use serde::Serialize;
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::ops::Range;
+use std::path::Path;
+
+// grep's default colours
+const PATH_COLOR: &str = "\x1b[35m";
+const LINE_NUMBER_COLOR: &str = "\x1b[32m";
+const MATCH_COLOR: &str = "\x1b[1;31m";
+const SEPARATOR_COLOR: &str = "\x1b[36m";
+const RESET: &str = "\x1b[0m";
+
+/// One line of `--json` output.
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Record<'a> {
+    Match(LineRecord<'a>),
+    Context(LineRecord<'a>),
+    Summary(&'a Stats),
+}
+
+#[derive(Serialize)]
+struct LineRecord<'a> {
+    path: Cow<'a, str>,
+    line_number: usize,
+    /// Where the line starts in the file, counting from 0.
+    byte_offset: u64,
+    text: &'a str,
+    spans: Vec<Span<'a>>,
+}
+
+/// Where a line is in its file.
+#[derive(Clone, Copy)]
+struct Position {
+    line_number: usize,
+    byte_offset: u64,
+}
+
+/// A match within a line, as byte offsets into its text.
+#[derive(Serialize)]
+struct Span<'a> {
+    start: usize,
+    end: usize,
+    text: &'a str,
+}
+
+/// Writes matching lines in grep's format: `path:line:text` for matches and
+/// `path-line-text` for context, with `--` between groups of lines that are
+/// not next to each other. With `-c`, `-l` or `-L` it writes one summary
+/// line per file instead, and with `--json` one JSON object per line.
+#[derive(Clone)]
+pub struct Printer {
+    pub show_path: bool,
+    pub line_number: bool,
+    pub before_context: usize,
+    pub after_context: usize,
+    pub color: bool,
+    pub invert: bool,
+    pub max_count: Option<usize>,
+    pub output: OutputMode,
+    pub json: bool,
+    /// What to replace each match with when printing.
+    pub replace: Option<String>,
+    /// The replacement to rewrite files with, when they are rewritten in
+    /// place rather than printed.
+    pub in_place: Option<String>,
+    /// Decompress inputs that look compressed before searching them.
+    pub decompress: bool,
+    // whether anything has been printed yet, so the next group of context
+    // lines knows to start with a separator
+    printed_group: bool,
+}
+
+impl Printer {
+    pub fn new(config: &Config, show_path: bool) -> Printer {
+        let color = match config.color {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => io::stdout().is_terminal(),
+        };
+
+        Printer {
+            show_path,
+            line_number: config.line_number,
+            before_context: config.before_context,
+            after_context: config.after_context,
+            color,
+            invert: config.invert,
+            max_count: config.max_count,
+            output: config.output,
+            json: config.json,
+            replace: config.replace.clone().filter(|_| !config.in_place),
+            in_place: config.replace.clone().filter(|_| config.in_place),
+            decompress: config.decompress,
+            printed_group: false,
+        }
+    }
+
+    /// A copy of this printer's settings that has not printed anything yet,
+    /// for buffering one file's output on another thread.
+    pub fn fresh(&self) -> Printer {
+        Printer {
+            printed_group: false,
+            ..self.clone()
+        }
+    }
+
+    /// Writes output buffered by a `fresh` copy of this printer, adding the
+    /// separator that would have been printed between the two files.
+    pub fn write_output(&mut self, out: &mut impl Write, output: &[u8]) -> io::Result<()> {
+        if output.is_empty() {
+            return Ok(());
+        }
+        if self.separates_groups() && self.printed_group {
+            self.write_separator(out)?;
+        }
+        self.printed_group = true;
+        out.write_all(output)
+    }
+
+    /// Writes the `--json` summary record for a whole search.
+    pub fn write_summary(&self, out: &mut impl Write, stats: &Stats) -> io::Result<()> {
+        serde_json::to_writer(&mut *out, &Record::Summary(stats))?;
+        writeln!(out)
+    }
+
+    /// Prints the lines read from `reader` that `matcher` selects, along
+    /// with any requested context. Lines are streamed, so memory use only
+    /// grows with the amount of before context. Returns the number of
+    /// selected lines.
+    pub fn print_file(
+        &mut self,
+        out: &mut impl Write,
+        path: &Path,
+        reader: impl BufRead,
+        matcher: &Matcher,
+    ) -> io::Result<usize> {
+        let separates_groups = self.separates_groups();
+        let mut before: VecDeque<(Position, String)> = VecDeque::with_capacity(self.before_context);
+        let mut after_left = 0;
+        let mut last_printed = None;
+        let mut matches = 0;
+
+        let print_lines = self.output == OutputMode::Lines;
+        // one selected line is enough to decide -l and -L
+        let max_count = if print_lines || self.output == OutputMode::Count {
+            self.max_count
+        } else {
+            Some(1)
+        };
+
+        let mut lines = input::LineReader::new(reader);
+        let mut line_no = 0;
+
+        loop {
+            let byte_offset = lines.offset();
+            let Some(line) = lines.next_line()? else {
+                break;
+            };
+            line_no += 1;
+            let position = Position {
+                line_number: line_no,
+                byte_offset,
+            };
+
+            // like grep, keep printing context after the last allowed match
+            let reached_max = max_count.is_some_and(|max| matches >= max);
+            if reached_max && after_left == 0 {
+                break;
+            }
+
+            let selected = !reached_max && matcher.is_match(&line) != self.invert;
+            if selected && !print_lines {
+                matches += 1;
+            } else if selected {
+                let first = before.front().map_or(line_no, |(p, _)| p.line_number);
+                let joins_previous = last_printed.is_some_and(|last| last + 1 == first);
+                if separates_groups && self.printed_group && !joins_previous {
+                    self.write_separator(out)?;
+                }
+                self.printed_group = true;
+
+                for (position, context) in before.drain(..) {
+                    self.write_line(out, path, position, &context, '-', &[])?;
+                }
+                // inverted lines have no match to highlight or replace
+                let (text, spans) = match &self.replace {
+                    _ if self.invert => (line, Vec::new()),
+                    Some(replacement) => {
+                        let (text, spans) = matcher.replace(&line, replacement);
+                        (Cow::Owned(text), spans)
+                    }
+                    None => {
+                        let spans = matcher.find_iter(&line);
+                        (line, spans)
+                    }
+                };
+                self.write_line(out, path, position, &text, ':', &spans)?;
+
+                last_printed = Some(line_no);
+                after_left = self.after_context;
+                matches += 1;
+            } else if after_left > 0 {
+                self.write_line(out, path, position, &line, '-', &[])?;
+                last_printed = Some(line_no);
+                after_left -= 1;
+            } else if self.before_context > 0 {
+                if before.len() == self.before_context {
+                    before.pop_front();
+                }
+                before.push_back((position, line.into_owned()));
+            }
+        }
+
+        match self.output {
+            OutputMode::Lines => {}
+            OutputMode::Count => {
+                if self.show_path {
+                    self.write_colored(out, PATH_COLOR, &path.display().to_string())?;
+                    self.write_colored(out, SEPARATOR_COLOR, ":")?;
+                }
+                writeln!(out, "{}", matches)?;
+            }
+            OutputMode::FilesWithMatches | OutputMode::FilesWithoutMatch => {
+                let wanted = (matches > 0) == (self.output == OutputMode::FilesWithMatches);
+                if wanted {
+                    self.write_colored(out, PATH_COLOR, &path.display().to_string())?;
+                    writeln!(out)?;
+                }
+            }
+        }
+
+        Ok(matches)
+    }
+
+    /// Writes one selected line on its own, for searches that rank lines
+    /// rather than print them as they are read.
+    pub fn write_match(
+        &self,
+        out: &mut impl Write,
+        path: &Path,
+        line_number: usize,
+        byte_offset: u64,
+        line: &str,
+    ) -> io::Result<()> {
+        let position = Position {
+            line_number,
+            byte_offset,
+        };
+        self.write_line(out, path, position, line, ':', &[])
+    }
+
+    /// Whether `--` goes between groups of lines that are not next to each
+    /// other. JSON records carry their own line numbers instead.
+    fn separates_groups(&self) -> bool {
+        !self.json && (self.before_context > 0 || self.after_context > 0)
+    }
+
+    fn write_separator(&self, out: &mut impl Write) -> io::Result<()> {
+        if self.color {
+            writeln!(out, "{}--{}", SEPARATOR_COLOR, RESET)
+        } else {
+            writeln!(out, "--")
+        }
+    }
+
+    fn write_line(
+        &self,
+        out: &mut impl Write,
+        path: &Path,
+        position: Position,
+        line: &str,
+        separator: char,
+        spans: &[Range<usize>],
+    ) -> io::Result<()> {
+        if self.json {
+            let record = LineRecord {
+                path: path.to_string_lossy(),
+                line_number: position.line_number,
+                byte_offset: position.byte_offset,
+                text: line,
+                spans: spans
+                    .iter()
+                    .map(|span| Span {
+                        start: span.start,
+                        end: span.end,
+                        text: &line[span.clone()],
+                    })
+                    .collect(),
+            };
+            let record = if separator == ':' {
+                Record::Match(record)
+            } else {
+                Record::Context(record)
+            };
+            serde_json::to_writer(&mut *out, &record)?;
+            return writeln!(out);
+        }
+
+        if self.show_path {
+            self.write_colored(out, PATH_COLOR, &path.display().to_string())?;
+            self.write_colored(out, SEPARATOR_COLOR, &separator.to_string())?;
+        }
+        if self.line_number {
+            self.write_colored(out, LINE_NUMBER_COLOR, &position.line_number.to_string())?;
+            self.write_colored(out, SEPARATOR_COLOR, &separator.to_string())?;
+        }
+
+        let mut end = 0;
+        for span in spans {
+            write!(out, "{}", &line[end..span.start])?;
+            self.write_colored(out, MATCH_COLOR, &line[span.clone()])?;
+            end = span.end;
+        }
+        write!(out, "{}", &line[end..])?;
+        writeln!(out)
+    }
+
+    fn write_colored(&self, out: &mut impl Write, color: &str, text: &str) -> io::Result<()> {
+        if self.color && !text.is_empty() {
+            write!(out, "{}{}{}", color, text, RESET)
+        } else {
+            out.write_all(text.as_bytes())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTENTS: &str = "\
+one
+two match
+three
+four
+five
+six match
+seven
+eight match
+nine";
+
+    fn new_printer(args: &str, show_path: bool) -> Printer {
+        let args: Vec<String> = format!("minigrep {} match poem.txt", args)
+            .split_whitespace()
+            .map(String::from)
+            .collect();
+        Printer::new(&Config::new(&args).unwrap(), show_path)
+    }
+
+    fn print(printer: &mut Printer, contents: &str) -> String {
+        let config = Config::new(&[
+            String::from("minigrep"),
+            String::from("match"),
+            String::from("."),
+        ])
+        .unwrap();
+        let matcher = Matcher::new(&config).unwrap();
+        let mut out = Vec::new();
+        printer
+            .print_file(
+                &mut out,
+                Path::new("poem.txt"),
+                contents.as_bytes(),
+                &matcher,
+            )
+            .unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn line_numbers_and_paths() {
+        let mut printer = new_printer("-n", true);
+        assert_eq!(
+            print(&mut printer, CONTENTS),
+            "poem.txt:2:two match\npoem.txt:6:six match\npoem.txt:8:eight match\n"
+        );
+    }
+
+    #[test]
+    fn context_groups_are_separated() {
+        let mut printer = new_printer("-n -C 1", false);
+        assert_eq!(
+            print(&mut printer, CONTENTS),
+            "\
+1-one
+2:two match
+3-three
+--
+5-five
+6:six match
+7-seven
+8:eight match
+9-nine
+"
+        );
+    }
+
+    #[test]
+    fn overlapping_context_is_not_repeated() {
+        let mut printer = new_printer("-B 3 -A 2", false);
+        assert_eq!(
+            print(&mut printer, CONTENTS),
+            "one\ntwo match\nthree\nfour\nfive\nsix match\nseven\neight match\nnine\n"
+        );
+    }
+
+    #[test]
+    fn separator_between_files() {
+        let mut printer = new_printer("-A 1", false);
+        let first = print(&mut printer, "a match\nb");
+        let second = print(&mut printer, "c match");
+        assert_eq!(first, "a match\nb\n");
+        assert_eq!(second, "--\nc match\n");
+    }
+
+    #[test]
+    fn highlights_matches() {
+        let mut printer = new_printer("-n --color always", false);
+        assert_eq!(
+            print(&mut printer, "a match, another match"),
+            "\x1b[32m1\x1b[0m\x1b[36m:\x1b[0ma \x1b[1;31mmatch\x1b[0m, another \x1b[1;31mmatch\x1b[0m\n"
+        );
+    }
+
+    #[test]
+    fn invert_match() {
+        let mut printer = new_printer("-v -n --color always", false);
+        assert_eq!(
+            print(&mut printer, "a match\nno\nyes match\nnope"),
+            "\x1b[32m2\x1b[0m\x1b[36m:\x1b[0mno\n\x1b[32m4\x1b[0m\x1b[36m:\x1b[0mnope\n"
+        );
+    }
+
+    #[test]
+    fn count() {
+        let mut printer = new_printer("-c", true);
+        assert_eq!(print(&mut printer, CONTENTS), "poem.txt:3\n");
+
+        let mut printer = new_printer("-c -v -m 2", false);
+        assert_eq!(print(&mut printer, CONTENTS), "2\n");
+    }
+
+    #[test]
+    fn files_with_and_without_matches() {
+        let mut printer = new_printer("-l", false);
+        assert_eq!(print(&mut printer, CONTENTS), "poem.txt\n");
+        assert_eq!(print(&mut printer, "nothing here"), "");
+
+        let mut printer = new_printer("-L", false);
+        assert_eq!(print(&mut printer, CONTENTS), "");
+        assert_eq!(print(&mut printer, "nothing here"), "poem.txt\n");
+    }
+
+    #[test]
+    fn max_count_keeps_trailing_context() {
+        let mut printer = new_printer("-m 1 -A 2", false);
+        assert_eq!(print(&mut printer, CONTENTS), "two match\nthree\nfour\n");
+
+        // a match inside the trailing context is printed as context
+        let mut printer = new_printer("-m 1 -A 1", false);
+        assert_eq!(
+            print(&mut printer, "a match\nb match\nc match"),
+            "a match\nb match\n"
+        );
+    }
+
+    #[test]
+    fn replaces_matches() {
+        let mut printer = new_printer("-r pin -n --color always", false);
+        assert_eq!(
+            print(&mut printer, "one\ntwo match, match"),
+            "\x1b[32m2\x1b[0m\x1b[36m:\x1b[0mtwo \x1b[1;31mpin\x1b[0m, \x1b[1;31mpin\x1b[0m\n"
+        );
+    }
+
+    #[test]
+    fn json_records() {
+        let mut printer = new_printer("--json -B 1", false);
+        let output = print(&mut printer, "one\r\ntwo match\nthree\nfour match");
+        let records: Vec<serde_json::Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        assert_eq!(
+            records[0],
+            serde_json::json!({
+                "type": "context",
+                "path": "poem.txt",
+                "line_number": 1,
+                "byte_offset": 0,
+                "text": "one",
+                "spans": [],
+            })
+        );
+        assert_eq!(
+            records[1],
+            serde_json::json!({
+                "type": "match",
+                "path": "poem.txt",
+                "line_number": 2,
+                "byte_offset": 5,
+                "text": "two match",
+                "spans": [{"start": 4, "end": 9, "text": "match"}],
+            })
+        );
+        // no `--` separator between the groups
+        assert_eq!(records.len(), 4);
+        assert_eq!(records[3]["byte_offset"], 21);
+    }
+
+    #[test]
+    fn json_summary() {
+        let printer = new_printer("--json", false);
+        let stats = Stats {
+            files_searched: 2,
+            files_with_matches: 1,
+            matched_lines: 3,
+            errors: 0,
+        };
+        let mut out = Vec::new();
+        printer.write_summary(&mut out, &stats).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "{\"type\":\"summary\",\"files_searched\":2,\"files_with_matches\":1,\"matched_lines\":3,\"errors\":0}\n"
+        );
+    }
+}
@@ -0,0 +1,179 @@
+use crate::matcher::Matcher;
+use crate::walk;
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Appended to a file's name to get the name of its backup.
+pub const BACKUP_SUFFIX: &str = ".bak";
+
+/// Replaces every match in the file at `path` with `replacement`, keeping
+/// the original next to it with `BACKUP_SUFFIX` added to its name. The new
+/// contents are written to a temporary file that is then renamed over the
+/// original, so the file is never left half written. Files without a match
+/// are not touched, and a file whose backup already exists is left as it is
+/// rather than lose the older backup. Returns the number of lines changed,
+/// or `None` if the file looks binary or is itself a backup.
+pub fn rewrite_file(
+    path: &Path,
+    matcher: &Matcher,
+    replacement: &str,
+) -> io::Result<Option<usize>> {
+    if path.as_os_str().to_string_lossy().ends_with(BACKUP_SUFFIX) {
+        return Ok(None);
+    }
+    let bytes = fs::read(path)?;
+    if walk::is_binary(&bytes) {
+        return Ok(None);
+    }
+    let contents = String::from_utf8(bytes).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            "not valid UTF-8, so it was not rewritten",
+        )
+    })?;
+
+    let mut rewritten = String::with_capacity(contents.len());
+    let mut changed = 0;
+    for line in contents.split_inclusive('\n') {
+        let text = line.trim_end_matches('\n').trim_end_matches('\r');
+        if matcher.is_match(text) {
+            rewritten.push_str(&matcher.replace(text, replacement).0);
+            rewritten.push_str(&line[text.len()..]);
+            changed += 1;
+        } else {
+            rewritten.push_str(line);
+        }
+    }
+
+    if changed > 0 {
+        replace_contents(path, rewritten.as_bytes())?;
+    }
+    Ok(Some(changed))
+}
+
+fn with_suffix(path: &Path, prefix: &str, suffix: &str) -> PathBuf {
+    let mut name = OsString::from(prefix);
+    name.push(path.file_name().unwrap_or_default());
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+fn replace_contents(path: &Path, contents: &[u8]) -> io::Result<()> {
+    // in the same directory, as a rename cannot cross file systems
+    let temp = with_suffix(path, ".", &format!(".minigrep-{}", std::process::id()));
+    let written = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&temp)
+        .and_then(|mut file| {
+            file.write_all(contents)?;
+            file.set_permissions(fs::metadata(path)?.permissions())?;
+            file.sync_all()
+        });
+
+    let result = written
+        .and_then(|_| back_up(path))
+        .and_then(|_| fs::rename(&temp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+/// Copies `path` to its backup, failing if there already is one.
+fn back_up(path: &Path) -> io::Result<()> {
+    let backup = with_suffix(path, "", BACKUP_SUFFIX);
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&backup)
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::AlreadyExists => io::Error::new(
+                e.kind(),
+                format!(
+                    "`{}` already exists, so it was not rewritten",
+                    backup.display()
+                ),
+            ),
+            _ => e,
+        })?;
+    let copied = File::open(path)
+        .and_then(|mut original| io::copy(&mut original, &mut file))
+        .and_then(|_| file.set_permissions(fs::metadata(path)?.permissions()))
+        .and_then(|_| file.sync_all());
+    if copied.is_err() {
+        let _ = fs::remove_file(&backup);
+    }
+    copied
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+
+    fn matcher(args: &[&str]) -> Matcher {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        Matcher::new(&Config::new(&args).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn rewrites_matches_and_keeps_a_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.txt");
+        fs::write(&path, "colour one\r\nplain\ncolour, colour").unwrap();
+
+        let matcher = matcher(&["minigrep", "-E", r"col(ou)r", "."]);
+        assert_eq!(
+            rewrite_file(&path, &matcher, "col${1}r-ish").unwrap(),
+            Some(2)
+        );
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "colour-ish one\r\nplain\ncolour-ish, colour-ish"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.path().join("notes.txt.bak")).unwrap(),
+            "colour one\r\nplain\ncolour, colour"
+        );
+        // nothing is left behind but the file and its backup
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn second_run_keeps_the_first_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.txt");
+        let backup = dir.path().join("notes.txt.bak");
+        fs::write(&path, "colour").unwrap();
+
+        let matcher = matcher(&["minigrep", "colo", "."]);
+        assert_eq!(rewrite_file(&path, &matcher, "colo-").unwrap(), Some(1));
+        let err = rewrite_file(&path, &matcher, "colo-").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "colo-ur");
+        assert_eq!(fs::read_to_string(&backup).unwrap(), "colour");
+
+        // backups are never rewritten themselves
+        assert_eq!(rewrite_file(&backup, &matcher, "colo-").unwrap(), None);
+        assert_eq!(fs::read_to_string(&backup).unwrap(), "colour");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn leaves_files_without_matches_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let text = dir.path().join("text.txt");
+        let binary = dir.path().join("data.bin");
+        fs::write(&text, "nothing here").unwrap();
+        fs::write(&binary, b"needle\0").unwrap();
+
+        let matcher = matcher(&["minigrep", "needle", "."]);
+        assert_eq!(rewrite_file(&text, &matcher, "pin").unwrap(), Some(0));
+        assert_eq!(rewrite_file(&binary, &matcher, "pin").unwrap(), None);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+        assert_eq!(fs::read(&binary).unwrap(), b"needle\0");
+    }
+}
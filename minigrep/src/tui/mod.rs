@@ -0,0 +1,484 @@
+// `minigrep --interactive FILE`: a terminal UI that searches one file as the
+// query is typed.
+//
+//     typing / Backspace    edit the query
+//     Up, Down              move between matches (also Ctrl-P and Ctrl-N)
+//     PageUp, PageDown      move a page at a time
+//     Enter                 open the selected line in $VISUAL or $EDITOR
+//     Esc, Ctrl-C           quit
+//
+// The selected match is shown with the lines around it in a preview pane.
+
+use crate::decompress;
+use crate::input::LineReader;
+use crate::matcher::Matcher;
+use crate::Config;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Position, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use std::env;
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Lines of context shown around the selected match unless `-A`, `-B` or
+/// `-C` ask for something else.
+const DEFAULT_CONTEXT: usize = 3;
+
+/// How far PageUp and PageDown move the selection.
+const PAGE: usize = 10;
+
+/// What the event loop should do after a key press.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Action {
+    Continue,
+    Quit,
+    /// Open the file in an editor at this line number.
+    Open(usize),
+}
+
+/// Everything the interactive search shows, kept apart from the terminal so
+/// it can be tested.
+pub struct App {
+    path: PathBuf,
+    lines: Vec<String>,
+    /// The search options, with `patterns` replaced by the current query.
+    config: Config,
+    query: String,
+    matcher: Option<Matcher>,
+    /// Indexes into `lines` of the lines the query matches.
+    matches: Vec<usize>,
+    /// Index into `matches`.
+    selected: usize,
+    /// The first match shown in the list.
+    scroll: usize,
+    /// Why the query could not be used, or the editor could not be opened.
+    error: Option<String>,
+}
+
+impl App {
+    pub fn new(mut config: Config, path: PathBuf, lines: Vec<String>) -> App {
+        let query = config.patterns.first().cloned().unwrap_or_default();
+        config.patterns = vec![query.clone()];
+        let mut app = App {
+            path,
+            lines,
+            config,
+            query,
+            matcher: None,
+            matches: Vec::new(),
+            selected: 0,
+            scroll: 0,
+            error: None,
+        };
+        app.update_matches();
+        app
+    }
+
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    /// Line numbers of the matching lines, counting from 1.
+    pub fn matching_lines(&self) -> Vec<usize> {
+        self.matches.iter().map(|&i| i + 1).collect()
+    }
+
+    /// The line number of the selected match, counting from 1.
+    pub fn selected_line(&self) -> Option<usize> {
+        self.matches.get(self.selected).map(|&i| i + 1)
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> Action {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc => return Action::Quit,
+            KeyCode::Char('c') if ctrl => return Action::Quit,
+            KeyCode::Enter => {
+                if let Some(line) = self.selected_line() {
+                    return Action::Open(line);
+                }
+            }
+            KeyCode::Up => self.move_selection(-1),
+            KeyCode::Char('p') if ctrl => self.move_selection(-1),
+            KeyCode::Down => self.move_selection(1),
+            KeyCode::Char('n') if ctrl => self.move_selection(1),
+            KeyCode::PageUp => self.move_selection(-(PAGE as isize)),
+            KeyCode::PageDown => self.move_selection(PAGE as isize),
+            KeyCode::Backspace => {
+                self.query.pop();
+                self.update_matches();
+            }
+            KeyCode::Char(c) if !ctrl => {
+                self.query.push(c);
+                self.update_matches();
+            }
+            _ => {}
+        }
+        Action::Continue
+    }
+
+    fn move_selection(&mut self, by: isize) {
+        let last = self.matches.len().saturating_sub(1);
+        self.selected = self.selected.saturating_add_signed(by).min(last);
+    }
+
+    /// Searches again after the query changed, keeping the selection on the
+    /// same line if it still matches.
+    fn update_matches(&mut self) {
+        let previous = self.matches.get(self.selected).copied();
+        self.config.patterns = vec![self.query.clone()];
+
+        match Matcher::new(&self.config) {
+            Ok(matcher) => {
+                self.matches = (0..self.lines.len())
+                    .filter(|&i| matcher.is_match(&self.lines[i]) != self.config.invert)
+                    .collect();
+                self.matcher = Some(matcher);
+                self.error = None;
+            }
+            Err(e) => {
+                // keep showing the last good results while a regex is half
+                // typed; regex errors end with a one line summary
+                let message = e.to_string();
+                self.error = message.lines().last().map(String::from);
+                return;
+            }
+        }
+
+        self.selected = previous
+            .and_then(|line| self.matches.iter().position(|&i| i == line))
+            .unwrap_or(0);
+        self.scroll = 0;
+    }
+
+    fn spans(&self, line: &str) -> Vec<Range<usize>> {
+        match &self.matcher {
+            Some(matcher) if !self.config.invert => matcher.find_iter(line),
+            _ => Vec::new(),
+        }
+    }
+
+    fn highlighted<'a>(&self, line: &'a str, style: Style) -> Vec<Span<'a>> {
+        let mut spans = Vec::new();
+        let mut end = 0;
+        for span in self.spans(line) {
+            spans.push(Span::styled(&line[end..span.start], style));
+            spans.push(Span::styled(
+                &line[span.clone()],
+                style.fg(Color::Red).add_modifier(Modifier::BOLD),
+            ));
+            end = span.end;
+        }
+        spans.push(Span::styled(&line[end..], style));
+        spans
+    }
+
+    pub fn render(&mut self, frame: &mut Frame) {
+        let before = self.preview_context(self.config.before_context);
+        let after = self.preview_context(self.config.after_context);
+        let [query_area, list_area, preview_area] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(3),
+            Constraint::Length((before + after + 3) as u16),
+        ])
+        .areas(frame.area());
+
+        self.render_query(frame, query_area);
+        self.render_matches(frame, list_area);
+        self.render_preview(frame, preview_area, before, after);
+    }
+
+    fn preview_context(&self, requested: usize) -> usize {
+        if self.config.before_context == 0 && self.config.after_context == 0 {
+            DEFAULT_CONTEXT
+        } else {
+            requested
+        }
+    }
+
+    fn render_query(&self, frame: &mut Frame, area: Rect) {
+        let title = match &self.error {
+            Some(error) => Line::styled(format!(" {} ", error), Style::new().fg(Color::Red)),
+            None => Line::from(format!(
+                " {} of {} lines match ",
+                self.matches.len(),
+                self.lines.len()
+            )),
+        };
+        let block = Block::new().borders(Borders::ALL).title(title);
+        frame.render_widget(Paragraph::new(self.query.as_str()).block(block), area);
+
+        let cursor_x = area.x + 1 + self.query.chars().count() as u16;
+        frame.set_cursor_position(Position::new(
+            cursor_x.min(area.right().saturating_sub(2)),
+            area.y + 1,
+        ));
+    }
+
+    fn render_matches(&mut self, frame: &mut Frame, area: Rect) {
+        let block = Block::new()
+            .borders(Borders::ALL)
+            .title(format!(" {} ", self.path.display()));
+        let height = block.inner(area).height as usize;
+
+        // scroll just enough to keep the selection in view
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if height > 0 && self.selected >= self.scroll + height {
+            self.scroll = self.selected + 1 - height;
+        }
+
+        let number_width = self.lines.len().to_string().len();
+        let rows: Vec<Line> = self
+            .matches
+            .iter()
+            .enumerate()
+            .skip(self.scroll)
+            .take(height)
+            .map(|(i, &line)| {
+                let style = if i == self.selected {
+                    Style::new().add_modifier(Modifier::REVERSED)
+                } else {
+                    Style::new()
+                };
+                let mut spans = vec![Span::styled(
+                    format!("{:>width$}: ", line + 1, width = number_width),
+                    style.fg(Color::Green),
+                )];
+                spans.extend(self.highlighted(&self.lines[line], style));
+                Line::from(spans)
+            })
+            .collect();
+        frame.render_widget(Paragraph::new(rows).block(block), area);
+    }
+
+    fn render_preview(&self, frame: &mut Frame, area: Rect, before: usize, after: usize) {
+        let mut block = Block::new().borders(Borders::ALL);
+        let mut rows = Vec::new();
+
+        if let Some(&selected) = self.matches.get(self.selected) {
+            block = block.title(format!(" {}:{} ", self.path.display(), selected + 1));
+            let first = selected.saturating_sub(before);
+            let last = (selected + after).min(self.lines.len() - 1);
+            for i in first..=last {
+                let (separator, style) = if i == selected {
+                    (':', Style::new().add_modifier(Modifier::BOLD))
+                } else {
+                    ('-', Style::new().fg(Color::DarkGray))
+                };
+                let mut spans = vec![Span::styled(
+                    format!("{}{}", i + 1, separator),
+                    Style::new().fg(Color::Green),
+                )];
+                if i == selected {
+                    spans.extend(self.highlighted(&self.lines[i], style));
+                } else {
+                    spans.push(Span::styled(self.lines[i].as_str(), style));
+                }
+                rows.push(Line::from(spans));
+            }
+        }
+
+        frame.render_widget(Paragraph::new(rows).block(block), area);
+    }
+}
+
+/// Runs the interactive search on the single file named in `config`.
+pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
+    let path = PathBuf::from(&config.paths[0]);
+    let lines =
+        read_lines(&path, config.decompress).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut app = App::new(config, path, lines);
+
+    let mut terminal = ratatui::try_init()?;
+    let result = event_loop(&mut terminal, &mut app);
+    ratatui::try_restore()?;
+    result
+}
+
+fn event_loop(terminal: &mut DefaultTerminal, app: &mut App) -> Result<(), Box<dyn Error>> {
+    loop {
+        terminal.draw(|frame| app.render(frame))?;
+
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+
+        match app.handle_key(key) {
+            Action::Continue => {}
+            Action::Quit => return Ok(()),
+            Action::Open(line) => {
+                // hand the terminal over to the editor until it exits
+                ratatui::try_restore()?;
+                let opened = open_in_editor(&app.path, line);
+                *terminal = ratatui::try_init()?;
+                app.error = opened.err().map(|e| e.to_string());
+            }
+        }
+    }
+}
+
+fn read_lines(path: &Path, decompress: bool) -> io::Result<Vec<String>> {
+    let file = BufReader::new(File::open(path)?);
+    let reader: Box<dyn BufRead> = if decompress {
+        decompress::decoder(file)?
+    } else {
+        Box::new(file)
+    };
+
+    let mut lines = Vec::new();
+    let mut reader = LineReader::new(reader);
+    while let Some(line) = reader.next_line()? {
+        lines.push(line.into_owned());
+    }
+    Ok(lines)
+}
+
+/// Opens `path` at `line` with `$VISUAL` or `$EDITOR`, falling back to `vi`.
+/// The line is passed as `+LINE`, which vi, vim, nano and emacs understand.
+fn open_in_editor(path: &Path, line: usize) -> io::Result<()> {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| String::from("vi"));
+    // the variable may hold arguments too, as in `EDITOR="emacs -nw"`
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or("vi");
+
+    let status = Command::new(program)
+        .args(words)
+        .arg(format!("+{}", line))
+        .arg(path)
+        .status()
+        .map_err(|e| io::Error::new(e.kind(), format!("could not run `{}`: {}", program, e)))?;
+    if !status.success() {
+        return Err(io::Error::other(format!(
+            "`{}` exited with {}",
+            program, status
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::backend::TestBackend;
+    use ratatui::Terminal;
+
+    const LINES: &[&str] = &["one", "two needle", "three", "four", "five needle", "six"];
+
+    fn app(args: &[&str]) -> App {
+        let args: Vec<String> = ["minigrep", "--interactive"]
+            .iter()
+            .chain(args)
+            .chain(&["poem.txt"])
+            .map(|s| s.to_string())
+            .collect();
+        let config = Config::new(&args).unwrap();
+        let lines = LINES.iter().map(|s| s.to_string()).collect();
+        App::new(config, PathBuf::from("poem.txt"), lines)
+    }
+
+    fn press(app: &mut App, code: KeyCode) -> Action {
+        app.handle_key(KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    fn type_text(app: &mut App, text: &str) {
+        for c in text.chars() {
+            press(app, KeyCode::Char(c));
+        }
+    }
+
+    #[test]
+    fn results_follow_the_query() {
+        let mut app = app(&[]);
+        // an empty query matches everything
+        assert_eq!(app.matching_lines().len(), LINES.len());
+
+        type_text(&mut app, "needle");
+        assert_eq!(app.query(), "needle");
+        assert_eq!(app.matching_lines(), vec![2, 5]);
+
+        type_text(&mut app, "s");
+        assert!(app.matching_lines().is_empty());
+        assert_eq!(press(&mut app, KeyCode::Enter), Action::Continue);
+
+        press(&mut app, KeyCode::Backspace);
+        assert_eq!(app.matching_lines(), vec![2, 5]);
+    }
+
+    #[test]
+    fn moves_and_opens_the_selection() {
+        let mut app = app(&[]);
+        type_text(&mut app, "needle");
+        assert_eq!(app.selected_line(), Some(2));
+
+        press(&mut app, KeyCode::Down);
+        press(&mut app, KeyCode::Down);
+        assert_eq!(app.selected_line(), Some(5));
+        assert_eq!(press(&mut app, KeyCode::Enter), Action::Open(5));
+
+        press(&mut app, KeyCode::PageUp);
+        assert_eq!(app.selected_line(), Some(2));
+        assert_eq!(press(&mut app, KeyCode::Esc), Action::Quit);
+    }
+
+    #[test]
+    fn selection_stays_on_the_same_line() {
+        let mut app = app(&[]);
+        type_text(&mut app, "e");
+        press(&mut app, KeyCode::Down);
+        press(&mut app, KeyCode::Down);
+        assert_eq!(app.selected_line(), Some(3));
+
+        type_text(&mut app, "e");
+        assert_eq!(app.matching_lines(), vec![2, 3, 5]);
+        assert_eq!(app.selected_line(), Some(3));
+    }
+
+    #[test]
+    fn half_typed_regex_keeps_the_last_results() {
+        let mut app = app(&["-E"]);
+        type_text(&mut app, "t(");
+        assert!(app.error.is_some());
+        assert_eq!(app.matching_lines(), vec![2, 3]);
+
+        type_text(&mut app, "w)");
+        assert!(app.error.is_none());
+        assert_eq!(app.matching_lines(), vec![2]);
+    }
+
+    #[test]
+    fn renders_matches_and_context() {
+        let mut app = app(&["-C", "1"]);
+        type_text(&mut app, "five");
+
+        let mut terminal = Terminal::new(TestBackend::new(40, 12)).unwrap();
+        terminal.draw(|frame| app.render(frame)).unwrap();
+        let screen: Vec<String> = terminal
+            .backend()
+            .buffer()
+            .content()
+            .chunks(40)
+            .map(|row| row.iter().map(|cell| cell.symbol()).collect())
+            .collect();
+
+        assert!(screen[1].contains("five"));
+        assert!(screen[0].contains("1 of 6 lines match"));
+        assert!(screen[4].contains("5: five needle"));
+        assert!(screen[7].contains("poem.txt:5"));
+        assert!(screen[8].contains("4-four"));
+        assert!(screen[9].contains("5:five needle"));
+        assert!(screen[10].contains("6-six"));
+    }
+}
@@ -0,0 +1,79 @@
+use ignore::WalkBuilder;
+use std::path::PathBuf;
+
+/// How much of a file is checked for NUL bytes when deciding whether it is
+/// binary.
+const BINARY_CHECK_LEN: usize = 8 * 1024;
+
+/// Yields every file under `path`, descending into directories. Files
+/// matched by `.gitignore` or `.ignore` files and hidden files are skipped
+/// unless they are named directly. Files come out sorted by name so the
+/// output order is stable.
+pub fn walk(path: &str) -> impl Iterator<Item = Result<PathBuf, ignore::Error>> {
+    WalkBuilder::new(path)
+        .require_git(false)
+        .sort_by_file_name(|a, b| a.cmp(b))
+        .build()
+        .filter_map(|entry| match entry {
+            Ok(entry) if entry.file_type().is_some_and(|t| t.is_dir()) => None,
+            Ok(entry) => Some(Ok(entry.into_path())),
+            Err(e) => Some(Err(e)),
+        })
+}
+
+/// Treats a file as binary if a NUL byte shows up near its start, the same
+/// heuristic grep uses.
+pub fn is_binary(contents: &[u8]) -> bool {
+    contents[..contents.len().min(BINARY_CHECK_LEN)].contains(&0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn walks_directories_and_honours_ignore_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir(root.join("sub")).unwrap();
+        fs::write(root.join("a.txt"), "a").unwrap();
+        fs::write(root.join("sub/b.txt"), "b").unwrap();
+        fs::write(root.join("skip.log"), "skipped").unwrap();
+        fs::write(root.join(".hidden"), "hidden").unwrap();
+        fs::write(root.join(".gitignore"), "*.log\n").unwrap();
+
+        let found: Vec<PathBuf> = walk(&root.display().to_string())
+            .map(Result::unwrap)
+            .collect();
+
+        assert_eq!(found, vec![root.join("a.txt"), root.join("sub/b.txt")]);
+    }
+
+    #[test]
+    fn named_files_are_always_searched() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::write(root.join(".gitignore"), "*.log\n").unwrap();
+        fs::write(root.join("skip.log"), "skipped").unwrap();
+
+        let found: Vec<PathBuf> = walk(&root.join("skip.log").display().to_string())
+            .map(Result::unwrap)
+            .collect();
+
+        assert_eq!(found, vec![root.join("skip.log")]);
+    }
+
+    #[test]
+    fn missing_paths_are_errors() {
+        let mut found = walk("does/not/exist");
+        assert!(found.next().unwrap().is_err());
+    }
+
+    #[test]
+    fn binary_detection() {
+        assert!(!is_binary(b"plain text\n"));
+        assert!(is_binary(b"\x7fELF\x00\x01"));
+        assert!(!is_binary(b""));
+    }
+}
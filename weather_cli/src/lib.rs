@@ -1,12 +1,47 @@
 pub mod weather {
     // imports
+    use chrono::{DateTime, NaiveDate};
     use colored::*;
-    use serde::Deserialize;
-    
+    use reqwest::StatusCode;
+    use serde::de::{self, DeserializeOwned};
+    use serde::{Deserialize, Deserializer};
+    use std::env;
+    use std::fs;
+    use std::io::{self, Write};
+    use std::path::PathBuf;
+    use thiserror::Error;
+
+    // everything that can go wrong getting the weather, with messages meant
+    // for the person using the cli
+    #[derive(Debug, Error)]
+    pub enum WeatherError {
+        #[error("no API key found; set API_KEY in the environment or in a .env file")]
+        MissingApiKey,
+        #[error("could not find a city called \"{city}\" in \"{country_code}\"; check the spelling and the country code")]
+        CityNotFound { city: String, country_code: String },
+        #[error("the API key was not accepted; check API_KEY (new keys can take a couple of hours to start working)")]
+        InvalidApiKey,
+        #[error("too many requests for this API key; wait a minute and try again")]
+        RateLimited,
+        #[error("could not reach the weather service; check your internet connection ({0})")]
+        Network(#[source] reqwest::Error),
+        #[error("the weather service sent a response that could not be understood ({0})")]
+        MalformedResponse(#[source] serde_json::Error),
+        #[error("the weather service failed with status {0}; try again later")]
+        UnexpectedStatus(StatusCode),
+        #[error("could not read the saved response {path}")]
+        Fixture {
+            path: PathBuf,
+            #[source]
+            source: io::Error,
+        },
+    }
+
     // struct to deserialize the JSON response from the openweatherMap API
     #[derive(Debug, Deserialize)]
     pub struct WeatherResponse {
-        weather: Vec<Weather>,
+        #[serde(deserialize_with = "first_weather")]
+        weather: Weather,
         main: Main,
         wind: Wind,
         name: String,
@@ -18,6 +53,15 @@ pub mod weather {
         description: String,
     }
 
+    // the api sends a list of conditions, most important first; only the
+    // first is shown, so a response without any is treated as malformed
+    fn first_weather<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Weather, D::Error> {
+        Vec::<Weather>::deserialize(deserializer)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| de::Error::custom("no weather conditions"))
+    }
+
     // struct to represent the weather parameters
     #[derive(Debug, Deserialize)]
     pub struct Main {
@@ -32,26 +76,246 @@ pub mod weather {
         speed: f64,
     }
 
-    // function to get the weather information from the openweathermap api
+    // struct to deserialize the 5 day / 3 hour forecast response
+    #[derive(Debug, Deserialize)]
+    pub struct ForecastResponse {
+        list: Vec<ForecastEntry>,
+        city: City,
+    }
+
+    // struct to represent the forecast for one 3 hour step
+    #[derive(Debug, Deserialize)]
+    pub struct ForecastEntry {
+        dt: i64,
+        main: ForecastMain,
+        weather: Vec<Weather>,
+        rain: Option<Precipitation>,
+        snow: Option<Precipitation>,
+    }
+
+    // struct to represent the temperature range of a forecast step
+    #[derive(Debug, Deserialize)]
+    pub struct ForecastMain {
+        temp_min: f64,
+        temp_max: f64,
+    }
+
+    // struct to represent the rain or snow expected over a forecast step, in mm
+    #[derive(Debug, Deserialize)]
+    pub struct Precipitation {
+        #[serde(rename = "3h", default)]
+        three_hours: f64,
+    }
+
+    // struct to represent the city a forecast is for
+    #[derive(Debug, Deserialize)]
+    pub struct City {
+        name: String,
+        // offset from UTC in seconds
+        #[serde(default)]
+        timezone: i64,
+    }
+
+    // one day of a forecast, summarised from its 3 hour steps
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct DailySummary {
+        pub date: NaiveDate,
+        pub min: f64,
+        pub max: f64,
+        // rain and snow together, in mm
+        pub precipitation: f64,
+        // the conditions forecast most often that day
+        pub description: String,
+    }
+
+    // anything that can look up the weather for a city, so the cli can run
+    // against the real api or against saved responses
+    pub trait WeatherProvider {
+        fn current_weather(
+            &self,
+            city: &str,
+            country_code: &str,
+        ) -> Result<WeatherResponse, WeatherError>;
+
+        fn forecast(
+            &self,
+            city: &str,
+            country_code: &str,
+        ) -> Result<ForecastResponse, WeatherError>;
+    }
+
+    // provider that calls the openweathermap api
+    pub struct OpenWeatherMap {
+        api_key: String,
+        base_url: String,
+    }
+
+    impl OpenWeatherMap {
+        pub const DEFAULT_BASE_URL: &'static str = "https://api.openweathermap.org";
+
+        pub fn new(api_key: &str) -> OpenWeatherMap {
+            OpenWeatherMap {
+                api_key: api_key.to_string(),
+                base_url: OpenWeatherMap::DEFAULT_BASE_URL.to_string(),
+            }
+        }
+
+        // reads the key from API_KEY, and the server from WEATHER_API_URL if
+        // that is set
+        pub fn from_env() -> Result<OpenWeatherMap, WeatherError> {
+            let api_key = env::var("API_KEY")
+                .ok()
+                .filter(|key| !key.trim().is_empty())
+                .ok_or(WeatherError::MissingApiKey)?;
+            let provider = OpenWeatherMap::new(api_key.trim());
+            Ok(match env::var("WEATHER_API_URL") {
+                Ok(base_url) => provider.with_base_url(&base_url),
+                Err(_) => provider,
+            })
+        }
+
+        // point the provider at another server, such as a proxy or a mock
+        pub fn with_base_url(mut self, base_url: &str) -> OpenWeatherMap {
+            self.base_url = base_url.trim_end_matches('/').to_string();
+            self
+        }
+    }
+
+    impl WeatherProvider for OpenWeatherMap {
+        fn current_weather(
+            &self,
+            city: &str,
+            country_code: &str,
+        ) -> Result<WeatherResponse, WeatherError> {
+            self.get("weather", city, country_code)
+        }
+
+        fn forecast(
+            &self,
+            city: &str,
+            country_code: &str,
+        ) -> Result<ForecastResponse, WeatherError> {
+            self.get("forecast", city, country_code)
+        }
+    }
+
+    impl OpenWeatherMap {
+        // calls one of the /data/2.5 endpoints for a city
+        fn get<T: DeserializeOwned>(
+            &self,
+            endpoint: &str,
+            city: &str,
+            country_code: &str,
+        ) -> Result<T, WeatherError> {
+            let url = format!("{}/data/2.5/{}", self.base_url, endpoint);
+            // letting reqwest build the query escapes cities with spaces in them
+            let response = reqwest::blocking::Client::new()
+                .get(&url)
+                .query(&[
+                    ("q", format!("{},{}", city, country_code).as_str()),
+                    ("units", "metric"),
+                    ("appid", &self.api_key),
+                ])
+                .send()
+                .map_err(WeatherError::Network)?;
+
+            match response.status() {
+                StatusCode::OK => {}
+                StatusCode::NOT_FOUND => {
+                    return Err(WeatherError::CityNotFound {
+                        city: city.to_string(),
+                        country_code: country_code.to_string(),
+                    })
+                }
+                StatusCode::UNAUTHORIZED => return Err(WeatherError::InvalidApiKey),
+                StatusCode::TOO_MANY_REQUESTS => return Err(WeatherError::RateLimited),
+                status => return Err(WeatherError::UnexpectedStatus(status)),
+            }
+
+            let body = response.text().map_err(WeatherError::Network)?;
+            serde_json::from_str(&body).map_err(WeatherError::MalformedResponse)
+        }
+    }
+
+    // provider that reads saved api responses from a directory instead of the
+    // network, one file per city named like `london_gb.json`, and one per
+    // forecast named like `london_gb_forecast.json`
+    pub struct FixtureProvider {
+        dir: PathBuf,
+    }
+
+    impl FixtureProvider {
+        pub fn new(dir: impl Into<PathBuf>) -> FixtureProvider {
+            FixtureProvider { dir: dir.into() }
+        }
+
+        fn path(&self, city: &str, country_code: &str, suffix: &str) -> PathBuf {
+            let name = format!("{}_{}{}.json", city, country_code, suffix)
+                .to_lowercase()
+                .replace(' ', "_");
+            self.dir.join(name)
+        }
+    }
+
+    impl WeatherProvider for FixtureProvider {
+        fn current_weather(
+            &self,
+            city: &str,
+            country_code: &str,
+        ) -> Result<WeatherResponse, WeatherError> {
+            self.read(city, country_code, "")
+        }
+
+        fn forecast(
+            &self,
+            city: &str,
+            country_code: &str,
+        ) -> Result<ForecastResponse, WeatherError> {
+            self.read(city, country_code, "_forecast")
+        }
+    }
+
+    impl FixtureProvider {
+        fn read<T: DeserializeOwned>(
+            &self,
+            city: &str,
+            country_code: &str,
+            suffix: &str,
+        ) -> Result<T, WeatherError> {
+            let path = self.path(city, country_code, suffix);
+            let text = match fs::read_to_string(&path) {
+                Ok(text) => text,
+                // no saved response is the same as the api not knowing the city
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                    return Err(WeatherError::CityNotFound {
+                        city: city.to_string(),
+                        country_code: country_code.to_string(),
+                    })
+                }
+                Err(source) => return Err(WeatherError::Fixture { path, source }),
+            };
+            serde_json::from_str(&text).map_err(WeatherError::MalformedResponse)
+        }
+    }
 
+    // function to get the weather information from the openweathermap api
     pub fn get_weather(
         city: &str,
         country_code: &str,
         api_key: &str,
-    ) -> Result<WeatherResponse, reqwest::Error> {
-        let url: String = format!(
-            "https://api.openweathermap.org/data/2.5/weather?q={},{}&units=metric&appid={}",
-            city, country_code, api_key
-        );
-        let response = reqwest::blocking::get(&url)?;
-        let res_json: WeatherResponse = response.json::<WeatherResponse>()?;
-        Ok(res_json)
+    ) -> Result<WeatherResponse, WeatherError> {
+        OpenWeatherMap::new(api_key).current_weather(city, country_code)
     }
 
     // Function to display the weather information
     pub fn display_weather_info(response: &WeatherResponse) {
+        write_weather_info(&mut io::stdout(), response).expect("Failed to write to stdout");
+    }
+
+    // Writes the weather information to any output, so it can be checked in tests
+    pub fn write_weather_info(out: &mut impl Write, response: &WeatherResponse) -> io::Result<()> {
         // Extract weather information from the response
-        let description: &String = &response.weather[0].description;
+        let description: &String = &response.weather.description;
         let temperature: f64 = response.main.temp;
         let humidity: f64 = response.main.humidity;
         let pressure: f64 = response.main.pressure;
@@ -73,30 +337,260 @@ pub mod weather {
             wind_speed
         );
 
-        // Colouring the weather text based on weather condition
-        let weather_text_colour: ColoredString = match description.as_str() {
-            "clear sky" => weather_text.bright_yellow(),
-            "few clouds" | "scattered clouds" | "broken clouds" => weather_text.bright_blue(),
+        writeln!(out, "{}", colour_for(description, weather_text))
+    }
+
+    // Colouring text based on weather condition
+    fn colour_for(description: &str, text: String) -> ColoredString {
+        match description {
+            "clear sky" => text.bright_yellow(),
+            "few clouds" | "scattered clouds" | "broken clouds" => text.bright_blue(),
             "dust" | "fog" | "squalls" | "smoke" | "sand" | "mist" | "haze" | "overcast clouds" => {
-                weather_text.dimmed()
+                text.dimmed()
+            }
+            "shower rain" | "rain" | "thunderstorm" | "snow" => text.bright_cyan(),
+            _ => text.normal(),
+        }
+    }
+
+    // Groups the 3 hour steps of a forecast into days, using the city's own
+    // time zone so a day runs from its local midnight
+    pub fn daily_summaries(forecast: &ForecastResponse) -> Vec<DailySummary> {
+        let mut days: Vec<DailySummary> = Vec::new();
+        // how often each description comes up on the day being summarised
+        let mut descriptions: Vec<(String, usize)> = Vec::new();
+
+        for entry in &forecast.list {
+            let Some(time) = DateTime::from_timestamp(entry.dt + forecast.city.timezone, 0) else {
+                continue;
+            };
+            let date = time.date_naive();
+            let precipitation = entry.rain.as_ref().map_or(0.0, |p| p.three_hours)
+                + entry.snow.as_ref().map_or(0.0, |p| p.three_hours);
+
+            match days.last_mut() {
+                Some(day) if day.date == date => {
+                    day.min = day.min.min(entry.main.temp_min);
+                    day.max = day.max.max(entry.main.temp_max);
+                    day.precipitation += precipitation;
+                }
+                _ => {
+                    descriptions.clear();
+                    days.push(DailySummary {
+                        date,
+                        min: entry.main.temp_min,
+                        max: entry.main.temp_max,
+                        precipitation,
+                        description: String::new(),
+                    });
+                }
             }
-            "shower rain" | "rain" | "thunderstorm" | "snow" => weather_text.bright_cyan(),
-            _ => weather_text.normal(),
-        };
 
-        println!("{}", weather_text_colour);
+            if let Some(weather) = entry.weather.first() {
+                match descriptions
+                    .iter_mut()
+                    .find(|(d, _)| *d == weather.description)
+                {
+                    Some((_, count)) => *count += 1,
+                    None => descriptions.push((weather.description.clone(), 1)),
+                }
+                // the earliest of the most common descriptions wins a tie
+                let mut most_common = &descriptions[0];
+                for candidate in &descriptions {
+                    if candidate.1 > most_common.1 {
+                        most_common = candidate;
+                    }
+                }
+                days.last_mut().unwrap().description = most_common.0.clone();
+            }
+        }
+        days
+    }
+
+    // Function to display the forecast
+    pub fn display_forecast(forecast: &ForecastResponse) {
+        write_forecast(&mut io::stdout(), forecast).expect("Failed to write to stdout");
+    }
+
+    // Writes the forecast as a table with a row per day, each row coloured
+    // like display_weather_info colours the current weather
+    pub fn write_forecast(out: &mut impl Write, forecast: &ForecastResponse) -> io::Result<()> {
+        let days = daily_summaries(forecast);
+        writeln!(
+            out,
+            "{}",
+            format!("Forecast for {}:", forecast.city.name).bold()
+        )?;
+        writeln!(
+            out,
+            "  {:<10}  {:>7}  {:>7}  {:>8}  Conditions",
+            "Day", "Min", "Max", "Rain"
+        )?;
+
+        for day in &days {
+            let row = format!(
+                "  {:<10}  {:>5.1}°C  {:>5.1}°C  {:>5.1} mm  {} {}",
+                day.date.format("%a %d %b"),
+                day.min,
+                day.max,
+                day.precipitation,
+                day.description,
+                get_emoji(day.max)
+            );
+            writeln!(out, "{}", colour_for(&day.description, row))?;
+        }
+        Ok(())
     }
 
     // function to get the emoji based on the temp
     pub fn get_emoji(temperature: f64) -> &'static str {
         if temperature < 0.0 {
             "❄️"
-        } else if temperature >= 0.0 && temperature < 10.0 {
+        } else if temperature < 10.0 {
             "☁️"
-        } else if temperature >= 10.0 && temperature < 20.0 {
+        } else if temperature < 20.0 {
             "🌤️"
         } else {
             "🔥"
         }
     }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn fixtures() -> FixtureProvider {
+            FixtureProvider::new(concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures"))
+        }
+
+        #[test]
+        fn reads_fixtures() {
+            let response = fixtures().current_weather("London", "GB").unwrap();
+            assert_eq!(response.name, "London");
+            assert_eq!(response.weather.description, "light rain");
+
+            let response = fixtures().current_weather("new york", "us").unwrap();
+            assert_eq!(response.name, "New York");
+
+            assert!(matches!(
+                fixtures().current_weather("Atlantis", "GR"),
+                Err(WeatherError::CityNotFound { .. })
+            ));
+        }
+
+        fn api_error(status: usize, body: &str) -> WeatherError {
+            let mut server = mockito::Server::new();
+            let _mock = server
+                .mock("GET", "/data/2.5/weather")
+                .match_query(mockito::Matcher::Any)
+                .with_status(status)
+                .with_body(body)
+                .create();
+            OpenWeatherMap::new("key")
+                .with_base_url(&server.url())
+                .current_weather("London", "GB")
+                .unwrap_err()
+        }
+
+        #[test]
+        fn maps_api_errors() {
+            assert!(matches!(
+                api_error(404, r#"{"cod":"404","message":"city not found"}"#),
+                WeatherError::CityNotFound { .. }
+            ));
+            assert!(matches!(api_error(401, "{}"), WeatherError::InvalidApiKey));
+            assert!(matches!(api_error(429, "{}"), WeatherError::RateLimited));
+            assert!(matches!(
+                api_error(500, ""),
+                WeatherError::UnexpectedStatus(StatusCode::INTERNAL_SERVER_ERROR)
+            ));
+            assert!(matches!(
+                api_error(200, r#"{"name":"London"}"#),
+                WeatherError::MalformedResponse(_)
+            ));
+            // well-formed JSON, but nothing to describe the weather with
+            let body = r#"{"weather":[],"main":{"temp":1.0,"humidity":2.0,"pressure":3.0},"wind":{"speed":4.0},"name":"London"}"#;
+            assert!(matches!(
+                api_error(200, body),
+                WeatherError::MalformedResponse(_)
+            ));
+        }
+
+        #[test]
+        fn network_failure() {
+            // nothing listens on port 1
+            let error = OpenWeatherMap::new("key")
+                .with_base_url("http://127.0.0.1:1")
+                .current_weather("London", "GB")
+                .unwrap_err();
+            assert!(matches!(error, WeatherError::Network(_)));
+        }
+
+        #[test]
+        fn messages_name_the_city() {
+            let error = WeatherError::CityNotFound {
+                city: String::from("Atlantis"),
+                country_code: String::from("GR"),
+            };
+            assert!(error.to_string().contains("\"Atlantis\" in \"GR\""));
+        }
+
+        #[test]
+        fn writes_weather_info() {
+            let response = fixtures().current_weather("London", "GB").unwrap();
+            let mut out = Vec::new();
+            write_weather_info(&mut out, &response).unwrap();
+            let text = String::from_utf8(out).unwrap();
+            assert!(text.contains("Weather in London: light rain 🌤️"));
+            assert!(text.contains("> Temperature: 12.3°C"));
+            assert!(text.contains("> Humidity: 81.0%"));
+            assert!(text.contains("> Pressure: 1012.0 hP"));
+            assert!(text.contains("> Wind Speed: 4.6 m/s"));
+        }
+
+        #[test]
+        fn summarises_forecast_days() {
+            let forecast = fixtures().forecast("London", "GB").unwrap();
+            let days = daily_summaries(&forecast);
+            let day = |d| NaiveDate::from_ymd_opt(2026, 10, d).unwrap();
+
+            assert_eq!(days.len(), 3);
+            assert_eq!(days[0].date, day(18));
+            assert_eq!((days[0].min, days[0].max), (9.1, 13.4));
+            assert!((days[0].precipitation - 2.5).abs() < 1e-9);
+            assert_eq!(days[0].description, "light rain");
+
+            // 21:00 UTC is already midnight in London
+            assert_eq!(days[1].date, day(19));
+            assert_eq!((days[1].min, days[1].max), (2.0, 9.5));
+            assert_eq!(days[1].precipitation, 0.0);
+            assert_eq!(days[1].description, "clear sky");
+
+            // snow counts too, and a tie goes to what came first
+            assert_eq!(days[2].date, day(20));
+            assert_eq!((days[2].min, days[2].max), (-1.5, 2.6));
+            assert!((days[2].precipitation - 2.4).abs() < 1e-9);
+            assert_eq!(days[2].description, "scattered clouds");
+        }
+
+        #[test]
+        fn writes_forecast_table() {
+            let forecast = fixtures().forecast("London", "GB").unwrap();
+            let mut out = Vec::new();
+            write_forecast(&mut out, &forecast).unwrap();
+            let text = String::from_utf8(out).unwrap();
+            assert!(text.contains("Forecast for London:"));
+            assert!(text.contains("  Day             Min      Max      Rain  Conditions"));
+            assert!(text.contains("  Sun 18 Oct    9.1°C   13.4°C    2.5 mm  light rain 🌤️"));
+            assert!(text.contains("  Tue 20 Oct   -1.5°C    2.6°C    2.4 mm  scattered clouds ☁️"));
+        }
+
+        #[test]
+        fn picks_emoji() {
+            assert_eq!(get_emoji(-5.0), "❄️");
+            assert_eq!(get_emoji(0.0), "☁️");
+            assert_eq!(get_emoji(15.0), "🌤️");
+            assert_eq!(get_emoji(25.0), "🔥");
+        }
+    }
 }
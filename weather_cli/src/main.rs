@@ -1,20 +1,47 @@
+use colored::*;
 use dotenvy::dotenv;
 use std::{
     env,
     io::{self},
+    process,
 };
 use weather_cli::weather::*;
-use colored::*;
-
 
+// picks where the weather comes from: saved responses when WEATHER_FIXTURES
+// names a directory of them, otherwise the openweathermap api, at
+// WEATHER_API_URL if that is set
+fn provider() -> Result<Box<dyn WeatherProvider>, WeatherError> {
+    if let Ok(dir) = env::var("WEATHER_FIXTURES") {
+        return Ok(Box::new(FixtureProvider::new(dir)));
+    }
+    Ok(Box::new(OpenWeatherMap::from_env()?))
+}
 
 fn main() {
     dotenv().ok();
+    // `weather_cli forecast` shows the next five days instead of the weather now
+    let forecast = match env::args().nth(1).as_deref() {
+        None => false,
+        Some("forecast") => true,
+        Some(other) => {
+            eprintln!(
+                "{} unknown mode {}; try `forecast`",
+                "Error:".bright_red(),
+                other
+            );
+            process::exit(1);
+        }
+    };
+    // without a key no search can work, so there is no point asking for a city
+    let provider = provider().unwrap_or_else(|error| {
+        eprintln!("{} {}", "Error:".bright_red(), error);
+        process::exit(1);
+    });
 
     println!("{}", "Welcome to the weather cli".bright_magenta());
     loop {
         // Reading in the country code
-        println!("{}", "Please enter a country code (US, CA, AU, etc...): ");
+        println!("Please enter a country code (US, CA, AU, etc...): ");
         let mut country = String::new();
         io::stdin()
             .read_line(&mut country)
@@ -22,10 +49,7 @@ fn main() {
         let country = country.trim();
 
         // Reading in the city
-        println!(
-            "{}",
-            "Please enter the name of a city within that country: "
-        );
+        println!("Please enter the name of a city within that country: ");
         let mut city = String::new();
         io::stdin()
             .read_line(&mut city)
@@ -33,23 +57,21 @@ fn main() {
         let city = city.trim();
 
         // fetching the weather information
-
-        let api_key = env::var("API_KEY").expect("API_KEY not set in env");
-
-        match get_weather(city, country, &api_key) {
-            Ok(response) => {
-                display_weather_info(&response);
-            }
-            Err(error) => {
-                eprintln!("Error {}", error);
-            }
+        let shown = if forecast {
+            provider
+                .forecast(city, country)
+                .map(|response| display_forecast(&response))
+        } else {
+            provider
+                .current_weather(city, country)
+                .map(|response| display_weather_info(&response))
+        };
+        if let Err(error) = shown {
+            eprintln!("{} {}", "Error:".bright_red(), error);
         }
 
         // asking if the user would like to continue
-        println!(
-            "{}",
-            "Do you want to search for weather in another City? (yes/no): "
-        );
+        println!("Do you want to search for weather in another City? (yes/no): ");
         let mut input = String::new();
         io::stdin()
             .read_line(&mut input)
@@ -0,0 +1,152 @@
+use assert_cmd::cargo::cargo_bin_cmd;
+use assert_cmd::Command;
+use predicates::prelude::*;
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures");
+
+// the cli with its answers to the prompts already typed in
+fn weather_cli(input: &str) -> Command {
+    let mut cmd = cargo_bin_cmd!("weather_cli");
+    cmd.env("WEATHER_FIXTURES", FIXTURES)
+        .env("NO_COLOR", "1")
+        .write_stdin(input);
+    cmd
+}
+
+#[test]
+fn shows_the_weather_for_a_city() {
+    weather_cli("GB\nLondon\nno\n")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Weather in London: light rain"))
+        .stdout(predicate::str::contains("> Temperature: 12.3°C"))
+        .stdout(predicate::str::contains(
+            "Thank you for using our software!",
+        ));
+}
+
+#[test]
+fn asks_again_until_told_to_stop() {
+    weather_cli("GB\nLondon\nyes\nUS\nNew York\nno\n")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Weather in London"))
+        .stdout(predicate::str::contains("Weather in New York: clear sky"));
+}
+
+#[test]
+fn reports_an_unknown_city_and_carries_on() {
+    weather_cli("GR\nAtlantis\nyes\nGB\nLondon\nno\n")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "Error: could not find a city called \"Atlantis\" in \"GR\"",
+        ))
+        .stdout(predicate::str::contains("Weather in London"));
+}
+
+#[test]
+fn calls_the_api_at_the_configured_url() {
+    let mut server = mockito::Server::new();
+    let fixture = std::fs::read_to_string(format!("{}/london_gb.json", FIXTURES)).unwrap();
+    let mock = server
+        .mock("GET", "/data/2.5/weather")
+        .match_query(mockito::Matcher::AllOf(vec![
+            mockito::Matcher::UrlEncoded("q".into(), "London,GB".into()),
+            mockito::Matcher::UrlEncoded("units".into(), "metric".into()),
+            mockito::Matcher::UrlEncoded("appid".into(), "test-key".into()),
+        ]))
+        .with_body(fixture)
+        .create();
+
+    let mut cmd = cargo_bin_cmd!("weather_cli");
+    cmd.env_remove("WEATHER_FIXTURES")
+        .env("API_KEY", "test-key")
+        .env("WEATHER_API_URL", server.url())
+        .write_stdin("GB\nLondon\nno\n")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Weather in London: light rain"));
+    mock.assert();
+}
+
+#[test]
+fn missing_api_key_is_explained() {
+    let mut cmd = cargo_bin_cmd!("weather_cli");
+    // an empty key counts as missing, and is not replaced by the one in .env
+    cmd.env_remove("WEATHER_FIXTURES")
+        .env("API_KEY", "")
+        .env("NO_COLOR", "1")
+        .write_stdin("GB\nLondon\nno\n")
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains(
+            "Error: no API key found; set API_KEY in the environment or in a .env file",
+        ));
+}
+
+#[test]
+fn invalid_api_key_is_explained() {
+    let mut server = mockito::Server::new();
+    let _mock = server
+        .mock("GET", "/data/2.5/weather")
+        .match_query(mockito::Matcher::Any)
+        .with_status(401)
+        .with_body(r#"{"cod":401,"message":"Invalid API key."}"#)
+        .create();
+
+    let mut cmd = cargo_bin_cmd!("weather_cli");
+    cmd.env_remove("WEATHER_FIXTURES")
+        .env("API_KEY", "wrong-key")
+        .env("WEATHER_API_URL", server.url())
+        .env("NO_COLOR", "1")
+        .write_stdin("GB\nLondon\nno\n")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "Error: the API key was not accepted",
+        ));
+}
+
+#[test]
+fn shows_a_forecast() {
+    weather_cli("GB\nLondon\nno\n")
+        .arg("forecast")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Forecast for London:"))
+        .stdout(predicate::str::contains(
+            "Mon 19 Oct    2.0°C    9.5°C    0.0 mm  clear sky",
+        ));
+}
+
+#[test]
+fn forecast_uses_the_forecast_endpoint() {
+    let mut server = mockito::Server::new();
+    let fixture = std::fs::read_to_string(format!("{}/london_gb_forecast.json", FIXTURES)).unwrap();
+    let mock = server
+        .mock("GET", "/data/2.5/forecast")
+        .match_query(mockito::Matcher::UrlEncoded("q".into(), "London,GB".into()))
+        .with_body(fixture)
+        .create();
+
+    let mut cmd = cargo_bin_cmd!("weather_cli");
+    cmd.arg("forecast")
+        .env_remove("WEATHER_FIXTURES")
+        .env("API_KEY", "test-key")
+        .env("WEATHER_API_URL", server.url())
+        .write_stdin("GB\nLondon\nno\n")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Sun 18 Oct"));
+    mock.assert();
+}
+
+#[test]
+fn unknown_mode_is_an_error() {
+    weather_cli("")
+        .arg("tomorrow")
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("unknown mode tomorrow"));
+}